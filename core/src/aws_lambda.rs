@@ -1,69 +1,583 @@
-// use lambda_extension::{tracing, Error, Extension, LambdaLog, LambdaLogRecord, Service, SharedService};
-// use std::{future::Future, pin::Pin, task::Poll};
-// use aws_sdk_lambda::primitives::Blob;
-// use tracing::span::Record;
-//
-// #[derive(Clone)]
-// struct FirehoseLogsProcessor {
-// }
-//
-// impl FirehoseLogsProcessor {
-//     pub fn new() -> Self {
-//     }
-// }
-//
-// /// Implementation of the actual log processor
-// ///
-// /// This receives a `Vec<LambdaLog>` whenever there are new log entries available.
-// impl Service<Vec<LambdaLog>> for FirehoseLogsProcessor {
-//     type Response = ();
-//     type Error = Error;
-//     type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
-//
-//     fn poll_ready(&mut self, _cx: &mut core::task::Context<'_>) -> core::task::Poll<Result<(), Self::Error>> {
-//         Poll::Ready(Ok(()))
-//     }
-//
-//     fn call(&mut self, logs: Vec<LambdaLog>) -> Self::Future {
-//         let mut records = Vec::with_capacity(logs.len());
-//
-//         for log in logs {
-//             match log.record {
-//                 LambdaLogRecord::Function(record) => {
-//                     records.push(Record::builder().data(Blob::new(record.as_bytes())).build())
-//                 }
-//                 _ => unreachable!(),
-//             }
-//         }
-//
-//         // let fut = self
-//         //     .client
-//         //     .put_record_batch()
-//         //     .set_records(Some(records))
-//         //     .delivery_stream_name(std::env::var("KINESIS_DELIVERY_STREAM").unwrap())
-//         //     .send();
-//         //
-//         // Box::pin(async move {
-//         //     let _ = fut.await?;
-//         //     Ok(())
-//         // })
-//         todo!()
-//     }
-// }
-//
-// #[tokio::main]
-// async fn main() -> Result<(), Error> {
-//     // required to enable CloudWatch error logging by the runtime
-//     tracing::init_default_subscriber();
-//
-//     let client = aws_config::load_defaults(aws_config::BehaviorVersion::v2023_11_09()).await;
-//     let logs_processor = SharedService::new(FirehoseLogsProcessor::new());
-//
-//     Extension::new()
-//         .with_log_types(&["function"])
-//         .with_logs_processor(logs_processor)
-//         .run()
-//         .await?;
-//
-//     Ok(())
-// }
\ No newline at end of file
+//! An internal AWS Lambda extension which flushes the background worker.
+//!
+//! Lambda freezes the execution environment as soon as an invocation's response is sent, so
+//! messages still queued in the [`BackgroundWorker`] would only be sent whenever the environment
+//! happens to be thawed again. Registering a [`LambdaExtension`] with the Lambda Extensions API
+//! keeps the environment running after each invocation until the worker has been flushed, and shuts
+//! the worker down when the environment is shut down.
+//!
+//! Messages can also link to the invocation's logs, through a shared [`LambdaContext`].
+//!
+//! ```ignore
+//! let context = LambdaContext::from_env();
+//! let (layer, worker) = SlackLayer::builder("my-function".into(), filters)
+//!     .lambda_context(context.clone())
+//!     .build();
+//! tracing_subscriber::registry().with(layer).init();
+//! worker.start().await;
+//!
+//! // Register before the runtime starts polling for invocations.
+//! let mut extension = LambdaExtension::new(worker);
+//! if let Some(context) = context {
+//!     extension = extension.lambda_context(context);
+//! }
+//! let extension = extension.register().await?;
+//! let invocations = extension.invocations();
+//! tokio::spawn(extension.run());
+//!
+//! lambda_runtime::run(service_fn(|event: LambdaEvent<Request>| async {
+//!     let request_id = event.context.request_id.clone();
+//!     let response = handler(event).await;
+//!     invocations.done(request_id);
+//!     response
+//! }))
+//! .await
+//! ```
+
+use std::{
+    fmt,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use debug_print::debug_println;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+use crate::{BackgroundWorker, LambdaInvocation};
+
+/// The version of the Lambda Extensions API used.
+const EXTENSION_API: &str = "2020-01-01/extension";
+
+/// The name the extension registers as, by default.
+const DEFAULT_EXTENSION_NAME: &str = "tracing-layer";
+
+/// How long before an invocation's deadline the extension stops waiting and flushes the worker.
+const DEADLINE_MARGIN: Duration = Duration::from_millis(200);
+
+/// An internal Lambda extension flushing a [`BackgroundWorker`] at the end of every invocation,
+/// and shutting it down when the execution environment shuts down.
+pub struct LambdaExtension {
+    worker: BackgroundWorker,
+    name: String,
+    runtime_api: Option<String>,
+    context: Option<LambdaContext>,
+}
+
+impl LambdaExtension {
+    /// Create an extension for the given worker, which must be started separately.
+    pub fn new(worker: BackgroundWorker) -> Self {
+        Self {
+            worker,
+            name: DEFAULT_EXTENSION_NAME.to_string(),
+            runtime_api: None,
+            context: None,
+        }
+    }
+
+    /// The name to register the extension as, `tracing-layer` by default.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// The `host:port` of the Lambda Runtime API, taken from the `AWS_LAMBDA_RUNTIME_API`
+    /// environment variable by default.
+    pub fn runtime_api(mut self, address: impl Into<String>) -> Self {
+        self.runtime_api = Some(address.into());
+        self
+    }
+
+    /// Record the request id of each invocation into the given context, which should also be given
+    /// to the layer's builder.
+    pub fn lambda_context(mut self, context: LambdaContext) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Register the extension for `INVOKE` and `SHUTDOWN` events.
+    ///
+    /// Internal extensions must register while the function initializes, before the runtime
+    /// starts polling for invocations.
+    pub async fn register(self) -> Result<RegisteredExtension, LambdaExtensionError> {
+        let runtime_api = match self.runtime_api {
+            Some(address) => address,
+            None => std::env::var("AWS_LAMBDA_RUNTIME_API").map_err(|_| LambdaExtensionError::MissingRuntimeApi)?,
+        };
+        let base_url = format!("http://{}/{}", runtime_api, EXTENSION_API);
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/register", base_url))
+            .header("Lambda-Extension-Name", &self.name)
+            .body(r#"{"events":["INVOKE","SHUTDOWN"]}"#)
+            .send()
+            .await?;
+        let response = check_status(response).await?;
+        let identifier = response
+            .headers()
+            .get("Lambda-Extension-Identifier")
+            .and_then(|value| value.to_str().ok())
+            .ok_or(LambdaExtensionError::MissingIdentifier)?
+            .to_string();
+        debug_println!("registered lambda extension {} as {}", self.name, identifier);
+
+        let (done, invocations_done) = mpsc::unbounded_channel();
+        Ok(RegisteredExtension {
+            worker: self.worker,
+            client,
+            base_url,
+            identifier,
+            context: self.context,
+            done,
+            invocations_done,
+        })
+    }
+}
+
+/// A [`LambdaExtension`] registered with the Extensions API, ready to [`run`](Self::run).
+pub struct RegisteredExtension {
+    worker: BackgroundWorker,
+    client: reqwest::Client,
+    base_url: String,
+    identifier: String,
+    context: Option<LambdaContext>,
+    done: mpsc::UnboundedSender<String>,
+    invocations_done: mpsc::UnboundedReceiver<String>,
+}
+
+impl RegisteredExtension {
+    /// A notifier for the function's handler to signal the end of each invocation.
+    pub fn invocations(&self) -> InvocationNotifier {
+        InvocationNotifier {
+            done: self.done.clone(),
+        }
+    }
+
+    /// Process events until the execution environment shuts down.
+    ///
+    /// For each invocation, waits until the handler signals it is done (or the invocation's
+    /// deadline nears), then flushes the worker before asking for the next event, which allows
+    /// Lambda to freeze the environment. Late signals for earlier invocations, such as one which
+    /// timed out, are ignored. On shutdown, the worker is shut down, sending any scheduled messages.
+    pub async fn run(mut self) -> Result<(), LambdaExtensionError> {
+        loop {
+            match self.next_event().await? {
+                NextEvent::Invoke(invoke) => {
+                    debug_println!("lambda extension received invocation {}", invoke.request_id);
+                    if let Some(context) = &self.context {
+                        context.set_request_id(invoke.request_id.clone());
+                    }
+                    let deadline = until_deadline(invoke.deadline_ms);
+                    let done = async {
+                        while let Some(request_id) = self.invocations_done.recv().await {
+                            if request_id == invoke.request_id {
+                                return;
+                            }
+                            debug_println!("lambda extension ignored late signal for invocation {}", request_id);
+                        }
+                    };
+                    if tokio::time::timeout(deadline, done).await.is_err() {
+                        println!(
+                            "ERROR: lambda invocation {} was not reported done before its deadline",
+                            invoke.request_id
+                        );
+                    }
+                    let deadline = until_deadline(invoke.deadline_ms);
+                    if tokio::time::timeout(deadline, self.worker.flush()).await.is_err() {
+                        println!("ERROR: timed out flushing webhook messages for invocation {}", invoke.request_id);
+                    }
+                }
+                NextEvent::Shutdown(shutdown) => {
+                    debug_println!("lambda extension shutting down: {}", shutdown.shutdown_reason);
+                    let deadline = until_deadline(shutdown.deadline_ms);
+                    if tokio::time::timeout(deadline, self.worker.clone().shutdown()).await.is_err() {
+                        println!("ERROR: timed out shutting down the webhook message worker");
+                    }
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    async fn next_event(&self) -> Result<NextEvent, LambdaExtensionError> {
+        let response = self
+            .client
+            .get(format!("{}/event/next", self.base_url))
+            .header("Lambda-Extension-Identifier", &self.identifier)
+            .send()
+            .await?;
+        let body = check_status(response).await?.text().await?;
+        serde_json::from_str(&body).map_err(|e| LambdaExtensionError::InvalidEvent(format!("{}: {}", e, body)))
+    }
+}
+
+/// The Lambda function the process runs as, and the invocation it is currently handling.
+///
+/// The function is read from the environment Lambda provides. The request id is recorded by a
+/// [`LambdaExtension`] given this context when each invocation starts, or may be set by the
+/// handler itself, which avoids attributing events emitted early in an invocation to the previous
+/// request. Clones share the current request id.
+#[derive(Debug, Clone)]
+pub struct LambdaContext {
+    function_name: String,
+    function_version: String,
+    region: Option<String>,
+    log_group: Option<String>,
+    log_stream: Option<String>,
+    request_id: Arc<RwLock<Option<String>>>,
+}
+
+impl LambdaContext {
+    /// Read the function from the `AWS_LAMBDA_FUNCTION_NAME`, `AWS_LAMBDA_FUNCTION_VERSION`,
+    /// `AWS_REGION`, `AWS_LAMBDA_LOG_GROUP_NAME` and `AWS_LAMBDA_LOG_STREAM_NAME` environment
+    /// variables. Returns `None` outside of Lambda.
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        Some(Self {
+            function_name: var("AWS_LAMBDA_FUNCTION_NAME")?,
+            function_version: var("AWS_LAMBDA_FUNCTION_VERSION").unwrap_or_else(|| "$LATEST".to_string()),
+            region: var("AWS_REGION"),
+            log_group: var("AWS_LAMBDA_LOG_GROUP_NAME"),
+            log_stream: var("AWS_LAMBDA_LOG_STREAM_NAME"),
+            request_id: Arc::default(),
+        })
+    }
+
+    /// Set the request id of the invocation being handled.
+    pub fn set_request_id(&self, request_id: impl Into<String>) {
+        *self.request_id.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(request_id.into());
+    }
+
+    /// The function and current invocation, to attach to a message.
+    pub(crate) fn invocation(&self) -> LambdaInvocation {
+        let request_id = self.request_id.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+        let log_url = match (&self.region, &self.log_group, &self.log_stream) {
+            (Some(region), Some(group), Some(stream)) => {
+                let mut url = format!(
+                    "https://{region}.console.aws.amazon.com/cloudwatch/home?region={region}\
+                     #logsV2:log-groups/log-group/{}/log-events/{}",
+                    console_encode(group),
+                    console_encode(stream),
+                    region = region,
+                );
+                if let Some(request_id) = &request_id {
+                    url.push_str("$3FfilterPattern$3D");
+                    url.push_str(&console_encode(&format!("\"{}\"", request_id)));
+                }
+                Some(url)
+            }
+            _ => None,
+        };
+        LambdaInvocation {
+            request_id,
+            function_name: self.function_name.clone(),
+            function_version: self.function_version.clone(),
+            log_url,
+        }
+    }
+}
+
+/// Encode a URL fragment the way the CloudWatch console expects: percent-encoded, with each `%`
+/// then replaced by `$25`.
+fn console_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("$25{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Signals the end of invocations to a [`RegisteredExtension`].
+#[derive(Debug, Clone)]
+pub struct InvocationNotifier {
+    done: mpsc::UnboundedSender<String>,
+}
+
+impl InvocationNotifier {
+    /// Signal that the handler has finished the invocation with the given request id, so the worker
+    /// may be flushed.
+    pub fn done(&self, request_id: impl Into<String>) {
+        let _ = self.done.send(request_id.into());
+    }
+}
+
+/// An event received from the Extensions API.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "eventType", rename_all = "SCREAMING_SNAKE_CASE")]
+pub(crate) enum NextEvent {
+    Invoke(InvokeEvent),
+    Shutdown(ShutdownEvent),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct InvokeEvent {
+    pub(crate) deadline_ms: u64,
+    pub(crate) request_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ShutdownEvent {
+    pub(crate) deadline_ms: u64,
+    pub(crate) shutdown_reason: String,
+}
+
+/// The time left until shortly before a deadline, in milliseconds since the epoch.
+fn until_deadline(deadline_ms: u64) -> Duration {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    Duration::from_millis(deadline_ms)
+        .saturating_sub(now)
+        .saturating_sub(DEADLINE_MARGIN)
+}
+
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response, LambdaExtensionError> {
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+        Err(LambdaExtensionError::Status(status, body))
+    }
+}
+
+/// An error produced while registering or running a [`LambdaExtension`].
+#[derive(Debug)]
+pub enum LambdaExtensionError {
+    /// No Runtime API address was given, and `AWS_LAMBDA_RUNTIME_API` is not set.
+    MissingRuntimeApi,
+    /// The registration response did not identify the extension.
+    MissingIdentifier,
+    /// A request to the Extensions API failed.
+    Request(reqwest::Error),
+    /// The Extensions API responded with an error status, and the response body.
+    Status(u16, String),
+    /// The Extensions API sent an event which could not be parsed.
+    InvalidEvent(String),
+}
+
+impl fmt::Display for LambdaExtensionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LambdaExtensionError::MissingRuntimeApi => write!(f, "AWS_LAMBDA_RUNTIME_API is not set"),
+            LambdaExtensionError::MissingIdentifier => write!(f, "the extension registration has no identifier"),
+            LambdaExtensionError::Request(e) => write!(f, "extensions API request failed: {}", e),
+            LambdaExtensionError::Status(status, body) => {
+                write!(f, "extensions API responded with status {}: {}", status, body)
+            }
+            LambdaExtensionError::InvalidEvent(e) => write!(f, "invalid extensions API event: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LambdaExtensionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LambdaExtensionError::Request(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for LambdaExtensionError {
+    fn from(e: reqwest::Error) -> Self {
+        LambdaExtensionError::Request(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::{Arc, Mutex},
+    };
+
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::{layer::WebhookLayer, Config, WebhookMessage, WebhookMessageFactory, WebhookMessageInputs};
+
+    /// A stand-in for the Lambda Runtime API, which also receives the webhook messages.
+    #[derive(Clone, Default)]
+    struct RuntimeApi {
+        requests: Arc<Mutex<Vec<String>>>,
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl RuntimeApi {
+        fn start(&self) -> String {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap().to_string();
+            let api = self.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let api = api.clone();
+                    std::thread::spawn(move || api.serve(stream.unwrap()));
+                }
+            });
+            address
+        }
+
+        fn serve(&self, stream: TcpStream) {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut stream = stream;
+            loop {
+                let mut request_line = String::new();
+                if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+                    return;
+                }
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let path = request_line.split_whitespace().nth(1).unwrap().to_string();
+                let (headers, body) = match path.as_str() {
+                    "/2020-01-01/extension/register" => ("Lambda-Extension-Identifier: ext-1\r\n", "{}".to_string()),
+                    "/2020-01-01/extension/event/next" => {
+                        let event = self.events.lock().unwrap().remove(0);
+                        ("", event)
+                    }
+                    _ => ("", String::from_utf8(body).unwrap()),
+                };
+                self.requests.lock().unwrap().push(match path.as_str() {
+                    "/webhook" => format!("webhook {}", body),
+                    _ => path,
+                });
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\n{}Content-Length: {}\r\n\r\n{}",
+                    headers,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct TestMessage(String, String);
+
+    impl WebhookMessage for TestMessage {
+        fn webhook_url(&self) -> &str {
+            &self.0
+        }
+
+        fn serialize(&self) -> String {
+            self.1.clone()
+        }
+    }
+
+    struct TestConfig(String);
+
+    impl Config for TestConfig {
+        fn webhook_url(&self) -> &str {
+            &self.0
+        }
+
+        fn new_from_env() -> Self {
+            TestConfig("http://localhost/webhook".to_string())
+        }
+    }
+
+    struct TestFactory;
+
+    impl WebhookMessageFactory for TestFactory {
+        fn create(inputs: WebhookMessageInputs) -> impl WebhookMessage {
+            let request_id = inputs.lambda.and_then(|lambda| lambda.request_id).unwrap_or_default();
+            TestMessage(inputs.webhook_url, format!("{} ({})", inputs.message, request_id))
+        }
+    }
+
+    #[test]
+    fn log_url_links_to_the_request() {
+        let context = LambdaContext {
+            function_name: "billing".to_string(),
+            function_version: "7".to_string(),
+            region: Some("eu-west-1".to_string()),
+            log_group: Some("/aws/lambda/billing".to_string()),
+            log_stream: Some("2024/01/01/[7]abc".to_string()),
+            request_id: Arc::default(),
+        };
+        context.set_request_id("req-1");
+        assert_eq!(
+            context.invocation().log_url.unwrap(),
+            "https://eu-west-1.console.aws.amazon.com/cloudwatch/home?region=eu-west-1\
+             #logsV2:log-groups/log-group/$252Faws$252Flambda$252Fbilling\
+             /log-events/2024$252F01$252F01$252F$255B7$255Dabc$3FfilterPattern$3D$2522req-1$2522"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn worker_is_flushed_before_the_next_event() {
+        let api = RuntimeApi::default();
+        let deadline_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() + 10_000;
+        *api.events.lock().unwrap() = vec![
+            format!(
+                r#"{{"eventType":"INVOKE","deadlineMs":{},"requestId":"req-1","invokedFunctionArn":"arn:aws:lambda:eu-west-1:123456789012:function:test"}}"#,
+                deadline_ms
+            ),
+            format!(r#"{{"eventType":"SHUTDOWN","deadlineMs":{},"shutdownReason":"spindown"}}"#, deadline_ms),
+        ];
+        let address = api.start();
+
+        let context = LambdaContext {
+            function_name: "billing".to_string(),
+            function_version: "$LATEST".to_string(),
+            region: None,
+            log_group: None,
+            log_stream: None,
+            request_id: Arc::default(),
+        };
+        let (layer, worker) = WebhookLayer::<TestConfig, TestFactory>::builder("test".into(), Default::default())
+            .config(TestConfig(format!("http://{}/webhook", address)))
+            .lambda_context(Some(context.clone()))
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        let dispatch = tracing::Dispatch::new(subscriber);
+        worker.start().await;
+
+        let extension = LambdaExtension::new(worker)
+            .runtime_api(address)
+            .lambda_context(context.clone())
+            .register()
+            .await
+            .unwrap();
+        let invocations = extension.invocations();
+        let extension = tokio::spawn(extension.run());
+        while context.invocation().request_id.is_none() {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        // A late signal from an earlier invocation, which must not flush this one early.
+        invocations.done("req-0");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        tracing::dispatcher::with_default(&dispatch, || tracing::error!("payment failed"));
+        invocations.done("req-1");
+        extension.await.unwrap().unwrap();
+
+        // The first event may be requested before or after the message is sent, but the next one
+        // only once the message has been sent.
+        let requests = api.requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 4, "{:?}", requests);
+        assert_eq!(requests[0], "/2020-01-01/extension/register");
+        assert!(requests[1..3].contains(&"webhook payment failed (req-1)".to_string()), "{:?}", requests);
+        assert!(requests[1..3].contains(&"/2020-01-01/extension/event/next".to_string()), "{:?}", requests);
+        assert_eq!(requests[3], "/2020-01-01/extension/event/next");
+    }
+}
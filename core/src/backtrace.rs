@@ -0,0 +1,156 @@
+use std::backtrace::Backtrace;
+
+use tracing::Level;
+
+/// Crates whose frames are dropped when no application crates are given.
+const INTERNAL_CRATES: [&str; 12] = [
+    "std",
+    "core",
+    "alloc",
+    "backtrace",
+    "test",
+    "tokio",
+    "futures_core",
+    "futures_util",
+    "tracing",
+    "tracing_core",
+    "tracing_subscriber",
+    "tracing_layer_core",
+];
+
+/// Captures a backtrace for severe events, trimmed to the frames of the application's crates.
+///
+/// Backtraces are captured regardless of `RUST_BACKTRACE`, since capturing is opted into here.
+#[derive(Debug, Clone)]
+pub struct BacktraceCapture {
+    level: Level,
+    crates: Vec<String>,
+}
+
+impl Default for BacktraceCapture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BacktraceCapture {
+    /// Capture backtraces for ERROR events, keeping every frame outside of the standard library,
+    /// the async runtime and tracing itself.
+    pub fn new() -> Self {
+        Self {
+            level: Level::ERROR,
+            crates: Vec::new(),
+        }
+    }
+
+    /// Capture backtraces for events at least as severe as the given level.
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Only keep the frames of the given crate, and of any other crate added this way. Both
+    /// package names (`my-app`) and crate names (`my_app`) are accepted.
+    pub fn app_crate(mut self, name: impl Into<String>) -> Self {
+        self.crates.push(name.into().replace('-', "_"));
+        self
+    }
+
+    /// Capture a trimmed backtrace of the calling thread, if the level calls for one.
+    pub(crate) fn capture(&self, level: Level) -> Option<String> {
+        if level > self.level {
+            return None;
+        }
+        self.capture_now()
+    }
+
+    /// Capture a trimmed backtrace of the calling thread, regardless of level.
+    pub(crate) fn capture_now(&self) -> Option<String> {
+        let trimmed = trim(&Backtrace::force_capture().to_string(), &self.crates);
+        (!trimmed.is_empty()).then_some(trimmed)
+    }
+}
+
+/// Keep the frames of a rendered backtrace belonging to the given crates (or, if none are given,
+/// to no internal crate), along with their source locations, and number them from zero.
+pub(crate) fn trim(backtrace: &str, crates: &[String]) -> String {
+    let mut trimmed = String::new();
+    let mut keep = false;
+    let mut frame = 0;
+    for line in backtrace.lines() {
+        let content = line.trim_start();
+        if let Some(symbol) = frame_symbol(content) {
+            keep = match symbol_crate(symbol) {
+                Some(name) if crates.is_empty() => !INTERNAL_CRATES.contains(&name),
+                Some(name) => crates.iter().any(|c| c == name),
+                None => false,
+            };
+            if keep {
+                trimmed.push_str(&format!("{:>4}: {}\n", frame, symbol));
+                frame += 1;
+            }
+        } else if keep && content.starts_with("at ") {
+            trimmed.push_str(&format!("        {}\n", content));
+        }
+    }
+    trimmed.truncate(trimmed.trim_end().len());
+    trimmed
+}
+
+/// The symbol of a line such as `12: my_app::main`.
+fn frame_symbol(line: &str) -> Option<&str> {
+    let (index, symbol) = line.split_once(": ")?;
+    index.chars().all(|c| c.is_ascii_digit()).then_some(symbol)
+}
+
+/// The crate of a symbol such as `my_app::main` or `<my_app::Type as core::fmt::Debug>::fmt`.
+/// Symbols without a path, such as `main` or `__libc_start_main`, belong to no crate.
+fn symbol_crate(symbol: &str) -> Option<&str> {
+    let symbol = symbol.trim_start_matches(['<', '&']);
+    symbol.split_once("::").map(|(name, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BACKTRACE: &str = "   0: std::backtrace::Backtrace::force_capture
+             at /rustc/library/std/src/backtrace.rs:312:13
+   1: tracing_layer_core::backtrace::BacktraceCapture::capture_now
+             at ./core/src/backtrace.rs:68:30
+   2: my_app::billing::charge
+             at ./src/billing.rs:42:9
+   3: <my_app::Worker as core::ops::Fn<()>>::call
+   4: serde_json::de::from_str
+             at /cargo/serde_json/src/de.rs:10:5
+   5: main
+   6: __libc_start_main";
+
+    #[test]
+    fn frames_are_trimmed_to_app_crates() {
+        assert_eq!(
+            trim(BACKTRACE, &["my_app".to_string()]),
+            "   0: my_app::billing::charge
+        at ./src/billing.rs:42:9
+   1: <my_app::Worker as core::ops::Fn<()>>::call"
+        );
+    }
+
+    #[test]
+    fn internal_frames_are_dropped_without_app_crates() {
+        let trimmed = trim(BACKTRACE, &[]);
+        assert!(trimmed.contains("my_app::billing::charge"));
+        assert!(trimmed.contains("serde_json::de::from_str"));
+        assert!(!trimmed.contains("std::backtrace"));
+        assert!(!trimmed.contains("tracing_layer_core"));
+        assert!(!trimmed.contains("main\n"));
+    }
+
+    #[test]
+    fn captured_backtraces_start_at_the_caller() {
+        let capture = BacktraceCapture::new().level(Level::WARN).app_crate("tracing-layer-core");
+        assert!(capture.capture(Level::INFO).is_none());
+        let backtrace = capture.capture(Level::WARN).unwrap();
+        assert!(backtrace.contains("captured_backtraces_start_at_the_caller"), "{}", backtrace);
+    }
+}
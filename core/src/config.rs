@@ -0,0 +1,695 @@
+use std::{collections::BTreeMap, fmt, path::Path, time::Duration};
+
+use regex::Regex;
+use serde::Deserialize;
+use tracing::{metadata::LevelFilter, Level};
+use tracing_subscriber::EnvFilter;
+
+use crate::{
+    filters::{EventFilters, PositiveMatch},
+    layer::WebhookLayerBuilder,
+    rate_limit::{RateLimit, Sampling},
+    schedule::{QuietWindow, Schedule, ScheduleAction},
+    FromWebhookUrl,
+    MessageFormat,
+    WebhookMessageFactory,
+};
+
+/// Declarative configuration for a whole webhook layer, deserializable from JSON, TOML (with the
+/// `toml` feature) or YAML (with the `yaml` feature).
+///
+/// ```toml
+/// app_name = "billing"
+/// level = "warn"
+/// format = "plain_text"
+/// escape_markup = true
+/// runbook_url = "https://wiki.example.com/runbooks/{target}"
+/// filter = "billing=info,payments=warn"
+/// exclude_fields = ["^password$"]
+/// redact_fields = ["token"]
+///
+/// [rate_limit]
+/// burst = 5
+/// period_secs = 60
+/// by_field = "user_id"
+///
+/// [sampling]
+/// info = 0.1
+///
+/// [context]
+/// environment = "production"
+///
+/// [schedule]
+/// timezone = "Europe/Berlin"
+///
+/// [[schedule.windows]]
+/// days = ["mon", "tue", "wed", "thu", "fri"]
+/// start = "22:00"
+/// end = "07:00"
+/// up_to = "warn"
+/// action = "digest"
+///
+/// [destination]
+/// webhook_url_env = "SLACK_BILLING_WEBHOOK_URL"
+///
+/// [targets]
+/// include = ["^billing", "^payments"]
+/// exclude = ["::internal"]
+/// match_any = true
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayerConfig {
+    /// The name of the application, included in every message.
+    pub app_name: String,
+    /// Where messages are sent. Defaults to the webhook URL in the environment.
+    #[serde(default)]
+    pub destination: Option<DestinationConfig>,
+    /// The most verbose level of events to send, e.g. `"warn"`.
+    #[serde(default)]
+    pub level: Option<String>,
+    /// Per-callsite directives in the `EnvFilter` syntax, e.g. `"my_app=warn,billing=info"`.
+    #[serde(default)]
+    pub filter: Option<String>,
+    /// Filter events by their target.
+    #[serde(default)]
+    pub targets: Option<FilterConfig>,
+    /// Filter events by their message.
+    #[serde(default)]
+    pub messages: Option<FilterConfig>,
+    /// Filter events by the keys of their fields.
+    #[serde(default)]
+    pub event_fields: Option<FilterConfig>,
+    /// Exclude event fields whose key matches any of these expressions.
+    #[serde(default)]
+    pub exclude_fields: Vec<String>,
+    /// Redact the values of fields whose key matches any of these expressions.
+    #[serde(default)]
+    pub redact_fields: Vec<String>,
+    /// Limit the number of events sent from each callsite.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+    /// The fraction of events to keep, per level, e.g. `info = 0.1`.
+    #[serde(default)]
+    pub sampling: BTreeMap<String, f64>,
+    /// Quiet hours and schedule-based routing.
+    #[serde(default)]
+    pub schedule: Option<ScheduleConfig>,
+    /// The layout of messages, `"rich"`, `"plain_text"` or `"attachments"`. Defaults to the
+    /// platform's default.
+    #[serde(default)]
+    pub format: Option<MessageFormat>,
+    /// Whether markup in logged text is escaped, see [`WebhookLayerBuilder::escape_markup`].
+    /// Defaults to `true`.
+    #[serde(default)]
+    pub escape_markup: Option<bool>,
+    /// Static context attached to every message, e.g. `environment = "production"`.
+    #[serde(default)]
+    pub context: BTreeMap<String, String>,
+    /// Links each message to a runbook, see [`WebhookLayerBuilder::runbook_url`].
+    #[serde(default)]
+    pub runbook_url: Option<String>,
+}
+
+/// Quiet hours and schedule-based routing, see [`Schedule`].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleConfig {
+    /// The IANA time zone the windows are in, e.g. `"Europe/Berlin"`.
+    pub timezone: String,
+    /// The windows, checked in order.
+    #[serde(default)]
+    pub windows: Vec<WindowConfig>,
+}
+
+/// A recurring window of a schedule, see [`QuietWindow`].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WindowConfig {
+    /// The days the window starts on, e.g. `"mon"`. Defaults to every day.
+    #[serde(default)]
+    pub days: Vec<String>,
+    /// The local time the window starts at, e.g. `"22:00"`.
+    pub start: String,
+    /// The local time the window ends at, e.g. `"07:00"`.
+    pub end: String,
+    /// Only apply the window to events no more severe than this level, e.g. `"warn"`.
+    #[serde(default)]
+    pub up_to: Option<String>,
+    /// One of `"suppress"`, `"digest"` or `"reroute"`.
+    pub action: String,
+    /// The webhook URL to reroute events to, when the action is `"reroute"`.
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+}
+
+/// A token-bucket rate limit, see [`RateLimit`].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// The number of events each callsite may send at once.
+    pub burst: u32,
+    /// The period over which the bucket is refilled.
+    pub period_secs: f64,
+    /// Key the limit by the value of this event field, in addition to the callsite.
+    #[serde(default)]
+    pub by_field: Option<String>,
+}
+
+/// Where the messages of a layer are sent.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DestinationConfig {
+    /// The webhook URL.
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    /// The name of an environment variable containing the webhook URL.
+    #[serde(default)]
+    pub webhook_url_env: Option<String>,
+}
+
+/// A set of regular expressions, see [`EventFilters`].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilterConfig {
+    /// Values must match these expressions.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Values must not match any of these expressions.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Values must match only one, rather than all, of the `include` expressions.
+    #[serde(default)]
+    pub match_any: bool,
+}
+
+impl LayerConfig {
+    /// Read a configuration file, choosing the format by its extension.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(ConfigError::Io)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json_str(&contents),
+            #[cfg(feature = "toml")]
+            Some("toml") => Self::from_toml_str(&contents),
+            #[cfg(feature = "yaml")]
+            Some("yaml") | Some("yml") => Self::from_yaml_str(&contents),
+            _ => Err(ConfigError::Parse(format!(
+                "unsupported configuration file format: {}",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Parse a JSON configuration.
+    pub fn from_json_str(contents: &str) -> Result<Self, ConfigError> {
+        serde_json::from_str(contents).map_err(|e| ConfigError::Parse(e.to_string()))
+    }
+
+    /// Parse a TOML configuration.
+    #[cfg(feature = "toml")]
+    pub fn from_toml_str(contents: &str) -> Result<Self, ConfigError> {
+        toml::from_str(contents).map_err(|e| ConfigError::Parse(e.to_string()))
+    }
+
+    /// Parse a YAML configuration.
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml_str(contents: &str) -> Result<Self, ConfigError> {
+        serde_yaml::from_str(contents).map_err(|e| ConfigError::Parse(e.to_string()))
+    }
+
+    /// Check the configuration, collecting every problem found rather than stopping at the first.
+    pub fn validate(&self) -> Result<ValidatedConfig, ConfigError> {
+        let mut issues = Issues::default();
+
+        if self.app_name.trim().is_empty() {
+            issues.push("app_name", "must not be empty");
+        }
+        let webhook_url = self
+            .destination
+            .as_ref()
+            .and_then(|destination| destination.resolve(&mut issues));
+        let level = self.level.as_ref().and_then(|level| {
+            level
+                .parse::<LevelFilter>()
+                .map_err(|e| issues.push("level", e))
+                .ok()
+        });
+        let directives = self.filter.as_ref().and_then(|directives| {
+            EnvFilter::try_new(directives)
+                .map_err(|e| issues.push("filter", e))
+                .ok()
+        });
+        let target_filters = self
+            .targets
+            .as_ref()
+            .map(|targets| targets.compile("targets", &mut issues))
+            .unwrap_or_default();
+        let message_filters = self
+            .messages
+            .as_ref()
+            .map(|messages| messages.compile("messages", &mut issues));
+        let event_by_field_filters = self
+            .event_fields
+            .as_ref()
+            .map(|event_fields| event_fields.compile("event_fields", &mut issues));
+        let field_exclusion_filters = compile_all("exclude_fields", &self.exclude_fields, &mut issues);
+        let field_redaction_filters = compile_all("redact_fields", &self.redact_fields, &mut issues);
+        let rate_limit = self
+            .rate_limit
+            .as_ref()
+            .and_then(|rate_limit| rate_limit.compile(&mut issues));
+        let sampling = self.compile_sampling(&mut issues);
+        let schedule = self.schedule.as_ref().and_then(|schedule| schedule.compile(&mut issues));
+
+        issues.into_result()?;
+        Ok(ValidatedConfig {
+            app_name: self.app_name.clone(),
+            webhook_url,
+            level,
+            directives,
+            target_filters,
+            message_filters,
+            event_by_field_filters,
+            field_exclusion_filters,
+            field_redaction_filters,
+            rate_limit,
+            sampling,
+            schedule,
+            format: self.format,
+            escape_markup: self.escape_markup,
+            context: self.context.clone(),
+            runbook_url: self.runbook_url.clone(),
+        })
+    }
+
+    fn compile_sampling(&self, issues: &mut Issues) -> Option<Sampling> {
+        if self.sampling.is_empty() {
+            return None;
+        }
+        let mut sampling = Sampling::default();
+        for (level, rate) in &self.sampling {
+            let field = format!("sampling.{}", level);
+            if !(0.0..=1.0).contains(rate) {
+                issues.push(&field, "must be between 0.0 and 1.0");
+            }
+            match level.parse::<Level>() {
+                Ok(level) => sampling = sampling.level(level, *rate),
+                Err(e) => issues.push(&field, e),
+            }
+        }
+        Some(sampling)
+    }
+}
+
+impl ScheduleConfig {
+    fn compile(&self, issues: &mut Issues) -> Option<Schedule> {
+        let timezone = self
+            .timezone
+            .parse::<chrono_tz::Tz>()
+            .map_err(|e| issues.push("schedule.timezone", e))
+            .ok();
+        let windows: Vec<_> = self
+            .windows
+            .iter()
+            .enumerate()
+            .map(|(i, window)| window.compile(&format!("schedule.windows[{}]", i), issues))
+            .collect();
+        let mut schedule = Schedule::new(timezone?);
+        for (window, action) in windows.into_iter().flatten() {
+            schedule = schedule.window(window, action);
+        }
+        Some(schedule)
+    }
+}
+
+impl WindowConfig {
+    fn compile(&self, field: &str, issues: &mut Issues) -> Option<(QuietWindow, ScheduleAction)> {
+        let time = |name: &str, value: &str, issues: &mut Issues| {
+            chrono::NaiveTime::parse_from_str(value, "%H:%M")
+                .map_err(|e| issues.push(&format!("{}.{}", field, name), format!("{:?}: {}", value, e)))
+                .ok()
+        };
+        let start = time("start", &self.start, issues);
+        let end = time("end", &self.end, issues);
+        let days: Vec<chrono::Weekday> = self
+            .days
+            .iter()
+            .filter_map(|day| {
+                day.parse()
+                    .map_err(|_| issues.push(&format!("{}.days", field), format!("invalid weekday {:?}", day)))
+                    .ok()
+            })
+            .collect();
+        let up_to = self.up_to.as_ref().and_then(|level| {
+            level
+                .parse::<Level>()
+                .map_err(|e| issues.push(&format!("{}.up_to", field), e))
+                .ok()
+        });
+        let action = match (self.action.as_str(), &self.webhook_url) {
+            ("suppress", None) => Some(ScheduleAction::Suppress),
+            ("digest", None) => Some(ScheduleAction::Digest),
+            ("reroute", Some(webhook_url)) => Some(ScheduleAction::Reroute(webhook_url.clone())),
+            ("reroute", None) => {
+                issues.push(&format!("{}.webhook_url", field), "required when the action is \"reroute\"");
+                None
+            }
+            ("suppress", Some(_)) | ("digest", Some(_)) => {
+                issues.push(&format!("{}.webhook_url", field), "only allowed when the action is \"reroute\"");
+                None
+            }
+            (other, _) => {
+                issues.push(
+                    &format!("{}.action", field),
+                    format!("expected \"suppress\", \"digest\" or \"reroute\", found {:?}", other),
+                );
+                None
+            }
+        };
+
+        let mut window = QuietWindow::new(start?, end?);
+        if !self.days.is_empty() {
+            window = window.days(days);
+        }
+        if let Some(level) = up_to {
+            window = window.up_to(level);
+        }
+        Some((window, action?))
+    }
+}
+
+impl RateLimitConfig {
+    fn compile(&self, issues: &mut Issues) -> Option<RateLimit> {
+        let mut valid = true;
+        if self.burst == 0 {
+            issues.push("rate_limit.burst", "must be greater than zero");
+            valid = false;
+        }
+        if !self.period_secs.is_finite() || self.period_secs <= 0.0 {
+            issues.push("rate_limit.period_secs", "must be greater than zero");
+            valid = false;
+        }
+        if !valid {
+            return None;
+        }
+        let rate_limit = RateLimit::new(self.burst, Duration::from_secs_f64(self.period_secs));
+        Some(match &self.by_field {
+            Some(field) => rate_limit.by_field(field.clone()),
+            None => rate_limit,
+        })
+    }
+}
+
+impl DestinationConfig {
+    fn resolve(&self, issues: &mut Issues) -> Option<String> {
+        match (&self.webhook_url, &self.webhook_url_env) {
+            (Some(_), Some(_)) => {
+                issues.push("destination", "set only one of webhook_url and webhook_url_env");
+                None
+            }
+            (Some(webhook_url), None) => Some(webhook_url.clone()),
+            (None, Some(env)) => std::env::var(env)
+                .map_err(|e| issues.push("destination.webhook_url_env", format!("{}: {}", env, e)))
+                .ok(),
+            (None, None) => None,
+        }
+    }
+}
+
+impl FilterConfig {
+    fn compile(&self, field: &str, issues: &mut Issues) -> EventFilters {
+        let include = compile_all(&format!("{}.include", field), &self.include, issues);
+        let exclude = compile_all(&format!("{}.exclude", field), &self.exclude, issues);
+        let positive_match = if self.match_any {
+            PositiveMatch::Any
+        } else {
+            PositiveMatch::All
+        };
+        EventFilters::new(include, exclude).positive_match(positive_match)
+    }
+}
+
+fn compile_all(field: &str, expressions: &[String], issues: &mut Issues) -> Option<Vec<Regex>> {
+    if expressions.is_empty() {
+        return None;
+    }
+    let compiled: Vec<Regex> = expressions
+        .iter()
+        .enumerate()
+        .filter_map(|(i, expression)| {
+            Regex::new(expression)
+                .map_err(|e| issues.push(&format!("{}[{}]", field, i), e))
+                .ok()
+        })
+        .collect();
+    Some(compiled)
+}
+
+/// A [`LayerConfig`] which has passed validation, with its expressions compiled.
+#[derive(Debug)]
+pub struct ValidatedConfig {
+    pub(crate) app_name: String,
+    pub(crate) webhook_url: Option<String>,
+    pub(crate) level: Option<LevelFilter>,
+    pub(crate) directives: Option<EnvFilter>,
+    pub(crate) target_filters: EventFilters,
+    pub(crate) message_filters: Option<EventFilters>,
+    pub(crate) event_by_field_filters: Option<EventFilters>,
+    pub(crate) field_exclusion_filters: Option<Vec<Regex>>,
+    pub(crate) field_redaction_filters: Option<Vec<Regex>>,
+    pub(crate) rate_limit: Option<RateLimit>,
+    pub(crate) sampling: Option<Sampling>,
+    pub(crate) schedule: Option<Schedule>,
+    pub(crate) format: Option<MessageFormat>,
+    pub(crate) escape_markup: Option<bool>,
+    pub(crate) context: BTreeMap<String, String>,
+    pub(crate) runbook_url: Option<String>,
+}
+
+impl<C: FromWebhookUrl, F: WebhookMessageFactory> WebhookLayerBuilder<C, F> {
+    /// Create a builder from a declarative configuration.
+    ///
+    /// The configuration is validated first, and every problem found is reported in the returned
+    /// error. The builder may be customized further before building the layer.
+    pub fn from_config(config: &LayerConfig) -> Result<Self, ConfigError> {
+        let validated = config.validate()?;
+        let mut builder = Self::new(validated.app_name, validated.target_filters);
+        if let Some(webhook_url) = validated.webhook_url {
+            builder = builder.config(C::new_from_webhook_url(webhook_url));
+        }
+        if let Some(level) = validated.level {
+            builder = builder.level_filter(level);
+        }
+        if let Some(directives) = validated.directives {
+            builder = builder.filter(directives);
+        }
+        if let Some(filters) = validated.message_filters {
+            builder = builder.message_filters(filters);
+        }
+        if let Some(filters) = validated.event_by_field_filters {
+            builder = builder.event_by_field_filters(filters);
+        }
+        if let Some(filters) = validated.field_exclusion_filters {
+            builder = builder.field_exclusion_filters(filters);
+        }
+        if let Some(filters) = validated.field_redaction_filters {
+            builder = builder.field_redaction_filters(filters);
+        }
+        if let Some(rate_limit) = validated.rate_limit {
+            builder = builder.rate_limit(rate_limit);
+        }
+        if let Some(sampling) = validated.sampling {
+            builder = builder.sampling(sampling);
+        }
+        if let Some(schedule) = validated.schedule {
+            builder = builder.schedule(schedule);
+        }
+        if let Some(format) = validated.format {
+            builder = builder.format(format);
+        }
+        if let Some(escape_markup) = validated.escape_markup {
+            builder = builder.escape_markup(escape_markup);
+        }
+        for (key, value) in validated.context {
+            builder = builder.context(key, value);
+        }
+        if let Some(runbook_url) = validated.runbook_url {
+            builder = builder.runbook_url(runbook_url);
+        }
+        Ok(builder)
+    }
+}
+
+/// A single problem found while validating a [`LayerConfig`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// The path of the offending setting, e.g. `targets.include[1]`.
+    pub field: String,
+    /// What is wrong with it.
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// An error produced while loading or validating a [`LayerConfig`].
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configuration file could not be read.
+    Io(std::io::Error),
+    /// The configuration could not be parsed.
+    Parse(String),
+    /// The configuration was parsed, but one or more settings are invalid.
+    Invalid(Vec<ConfigIssue>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "failed to read configuration: {}", e),
+            ConfigError::Parse(e) => write!(f, "failed to parse configuration: {}", e),
+            ConfigError::Invalid(issues) => {
+                write!(f, "invalid configuration ({} problems)", issues.len())?;
+                for issue in issues {
+                    write!(f, "\n  - {}", issue)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => Some(e),
+            ConfigError::Parse(_) | ConfigError::Invalid(_) => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Issues(Vec<ConfigIssue>);
+
+impl Issues {
+    fn push(&mut self, field: &str, message: impl fmt::Display) {
+        self.0.push(ConfigIssue {
+            field: field.to_string(),
+            message: message.to_string(),
+        });
+    }
+
+    fn into_result(self) -> Result<(), ConfigError> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(self.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validation_reports_every_problem() {
+        let config = LayerConfig::from_json_str(
+            r#"{
+                "app_name": "",
+                "level": "loud",
+                "destination": { "webhook_url": "https://example.com", "webhook_url_env": "URL" },
+                "targets": { "include": ["^ok", "(unclosed"] },
+                "redact_fields": ["[z-a]"],
+                "rate_limit": { "burst": 0, "period_secs": 60 },
+                "sampling": { "info": 0.5, "loud": 2.0 },
+                "schedule": {
+                    "timezone": "Europe/Berlin",
+                    "windows": [{ "start": "22:00", "end": "7am", "action": "reroute" }]
+                }
+            }"#,
+        )
+        .unwrap();
+        let issues = match config.validate() {
+            Err(ConfigError::Invalid(issues)) => issues,
+            other => panic!("expected invalid configuration, got {:?}", other),
+        };
+        let fields: Vec<&str> = issues.iter().map(|issue| issue.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "app_name",
+                "destination",
+                "level",
+                "targets.include[1]",
+                "redact_fields[0]",
+                "rate_limit.burst",
+                "sampling.loud",
+                "sampling.loud",
+                "schedule.windows[0].end",
+                "schedule.windows[0].webhook_url",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        assert!(matches!(
+            LayerConfig::from_json_str(r#"{ "app_name": "app", "levle": "warn" }"#),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_configuration_is_valid() {
+        let config = LayerConfig::from_toml_str(
+            r#"
+            app_name = "billing"
+            level = "warn"
+            filter = "billing=info"
+            exclude_fields = ["^password$"]
+
+            [destination]
+            webhook_url = "https://example.com/webhook"
+
+            [targets]
+            include = ["^billing", "^payments"]
+            match_any = true
+            "#,
+        )
+        .unwrap();
+        let validated = config.validate().unwrap();
+        assert_eq!(validated.webhook_url.as_deref(), Some("https://example.com/webhook"));
+        assert_eq!(validated.level, Some(LevelFilter::WARN));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_configuration_is_valid() {
+        let config = LayerConfig::from_yaml_str(
+            r##"
+            app_name: billing
+            level: warn
+            format: plain_text
+            context:
+              environment: production
+            destination:
+              webhook_url: https://example.com/webhook
+            rate_limit:
+              burst: 5
+              period_secs: 60
+            "##,
+        )
+        .unwrap();
+        let validated = config.validate().unwrap();
+        assert_eq!(validated.webhook_url.as_deref(), Some("https://example.com/webhook"));
+        assert_eq!(validated.level, Some(LevelFilter::WARN));
+        assert_eq!(validated.format, Some(MessageFormat::PlainText));
+        assert!(validated.rate_limit.is_some());
+    }
+}
@@ -0,0 +1,47 @@
+use std::error::Error;
+
+use tracing::field::{Field, Visit};
+
+/// An error recorded as a field of an event, with its chain of sources.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventError {
+    /// The name of the field the error was recorded in.
+    pub field: String,
+    /// The error's `Display` representation.
+    pub message: String,
+    /// The `Display` representation of each error in the `source()` chain, outermost first.
+    pub sources: Vec<String>,
+}
+
+/// A visitor capturing every field recorded with `record_error`, i.e. fields logged as
+/// `error = &err as &dyn std::error::Error`, along with the error's source chain.
+#[derive(Debug, Default)]
+pub(crate) struct ErrorChainVisitor {
+    errors: Vec<EventError>,
+}
+
+impl ErrorChainVisitor {
+    /// The captured errors, with any field named `error` first.
+    pub(crate) fn into_errors(mut self) -> Vec<EventError> {
+        self.errors.sort_by_key(|error| error.field != "error");
+        self.errors
+    }
+}
+
+impl Visit for ErrorChainVisitor {
+    fn record_error(&mut self, field: &Field, value: &(dyn Error + 'static)) {
+        let mut sources = Vec::new();
+        let mut source = value.source();
+        while let Some(error) = source {
+            sources.push(error.to_string());
+            source = error.source();
+        }
+        self.errors.push(EventError {
+            field: field.name().to_string(),
+            message: value.to_string(),
+            sources,
+        });
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
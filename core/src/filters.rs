@@ -1,44 +1,238 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::{Arc, Mutex, RwLock},
+    time::SystemTime,
+};
+
 use regex::Regex;
+use tracing::{
+    metadata::LevelFilter,
+    span,
+    subscriber::Interest,
+    Level,
+    Metadata,
+};
+use tracing_subscriber::{
+    layer::{self, Context},
+    EnvFilter,
+};
 
 pub trait Filter {
     fn process(&self, value: &str) -> Result<(), FilterError>;
+
+    /// Combine this filter with another, passing only values accepted by both.
+    fn and<B: Filter>(self, other: B) -> And<Self, B>
+    where
+        Self: Sized,
+    {
+        And(self, other)
+    }
+
+    /// Combine this filter with another, passing values accepted by either.
+    fn or<B: Filter>(self, other: B) -> Or<Self, B>
+    where
+        Self: Sized,
+    {
+        Or(self, other)
+    }
+
+    /// Invert this filter, passing only values it would reject.
+    fn not(self) -> Not<Self>
+    where
+        Self: Sized,
+    {
+        Not(self)
+    }
+}
+
+/// A single regex used as a positive filter: the value passes if it MATCHES the expression.
+impl Filter for Regex {
+    fn process(&self, value: &str) -> Result<(), FilterError> {
+        if self.is_match(value) {
+            Ok(())
+        } else {
+            Err(FilterError::PositiveFilterFailed)
+        }
+    }
+}
+
+impl<F: Filter + ?Sized> Filter for &F {
+    fn process(&self, value: &str) -> Result<(), FilterError> {
+        (**self).process(value)
+    }
+}
+
+impl<F: Filter + ?Sized> Filter for Box<F> {
+    fn process(&self, value: &str) -> Result<(), FilterError> {
+        (**self).process(value)
+    }
+}
+
+impl<F: Filter + ?Sized> Filter for Arc<F> {
+    fn process(&self, value: &str) -> Result<(), FilterError> {
+        (**self).process(value)
+    }
+}
+
+/// Passes a value only if both inner filters pass it.
+#[derive(Debug, Clone)]
+pub struct And<A, B>(pub A, pub B);
+
+impl<A: Filter, B: Filter> Filter for And<A, B> {
+    fn process(&self, value: &str) -> Result<(), FilterError> {
+        self.0.process(value)?;
+        self.1.process(value)
+    }
+}
+
+/// Passes a value if either inner filter passes it.
+#[derive(Debug, Clone)]
+pub struct Or<A, B>(pub A, pub B);
+
+impl<A: Filter, B: Filter> Filter for Or<A, B> {
+    fn process(&self, value: &str) -> Result<(), FilterError> {
+        self.0.process(value).or_else(|_| self.1.process(value))
+    }
+}
+
+/// Passes a value only if the inner filter rejects it.
+#[derive(Debug, Clone)]
+pub struct Not<F>(pub F);
+
+impl<F: Filter> Filter for Not<F> {
+    fn process(&self, value: &str) -> Result<(), FilterError> {
+        match self.0.process(value) {
+            Ok(()) => Err(FilterError::NegativeMatchFailed),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+/// Passes a value if at least one of the inner filters passes it.
+///
+/// An empty list passes nothing.
+#[derive(Debug, Clone)]
+pub struct AnyOf<F>(pub Vec<F>);
+
+impl<F: Filter> Filter for AnyOf<F> {
+    fn process(&self, value: &str) -> Result<(), FilterError> {
+        if self.0.iter().any(|filter| filter.process(value).is_ok()) {
+            Ok(())
+        } else {
+            Err(FilterError::PositiveFilterFailed)
+        }
+    }
+}
+
+/// Passes a value only if every inner filter passes it.
+///
+/// An empty list passes everything.
+#[derive(Debug, Clone)]
+pub struct AllOf<F>(pub Vec<F>);
+
+impl<F: Filter> Filter for AllOf<F> {
+    fn process(&self, value: &str) -> Result<(), FilterError> {
+        for filter in &self.0 {
+            filter.process(value)?;
+        }
+        Ok(())
+    }
+}
+
+/// How the positive expressions of an [`EventFilters`] are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PositiveMatch {
+    /// The value must MATCH every positive expression.
+    #[default]
+    All,
+    /// The value must MATCH at least one positive expression.
+    Any,
 }
 
 /// EventFilters describes two optional lists of regular expressions used to filter events.
 ///
 /// If provided, each expression is used in either negatively ("does NOT MATCH") or
 /// positively ("does MATCH") filter against a specified value.
-#[derive(Debug, Clone, Default)]
+#[derive(Clone, Default)]
 pub struct EventFilters {
     /// An optional list of one-or-more regular expressions to use for determining record inclusion.
     positive: Option<Vec<Regex>>,
     /// An optional list of one-or-more regular expressions to use for determining record exclusion.
     negative: Option<Vec<Regex>>,
+    /// Whether a value must match all, or only one, of the positive expressions.
+    positive_match: PositiveMatch,
+    /// An optional filter built from combinators, applied after the regular expressions.
+    custom: Option<Arc<dyn Filter + Send + Sync>>,
 }
 
 impl EventFilters {
     /// Create a new set of matches.
     pub fn new(positive: Option<Vec<Regex>>, negative: Option<Vec<Regex>>) -> Self {
-        Self { positive, negative }
+        Self {
+            positive,
+            negative,
+            positive_match: PositiveMatch::All,
+            custom: None,
+        }
+    }
+
+    /// Create a set of matches from an arbitrary filter, such as a combination of [`And`], [`Or`],
+    /// [`Not`] and [`AnyOf`].
+    pub fn from_filter<F: Filter + Send + Sync + 'static>(filter: F) -> Self {
+        Self::default().with_filter(filter)
+    }
+
+    /// Require a value to match at least one positive expression, rather than all of them.
+    pub fn match_any(mut self) -> Self {
+        self.positive_match = PositiveMatch::Any;
+        self
+    }
+
+    /// Configure how the positive expressions are combined.
+    pub fn positive_match(mut self, positive_match: PositiveMatch) -> Self {
+        self.positive_match = positive_match;
+        self
+    }
+
+    /// Additionally require a value to pass the given filter.
+    ///
+    /// Calling this more than once requires a value to pass every provided filter.
+    pub fn with_filter<F: Filter + Send + Sync + 'static>(mut self, filter: F) -> Self {
+        self.custom = Some(match self.custom.take() {
+            Some(existing) => Arc::new(And(existing, filter)),
+            None => Arc::new(filter),
+        });
+        self
+    }
+}
+
+impl fmt::Debug for EventFilters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventFilters")
+            .field("positive", &self.positive)
+            .field("negative", &self.negative)
+            .field("positive_match", &self.positive_match)
+            .field("custom", &self.custom.as_ref().map(|_| ".."))
+            .finish()
     }
 }
 
 impl Filter for EventFilters {
     fn process(&self, value: &str) -> Result<(), FilterError> {
         if let Some(negative) = &self.negative {
-            for filter in negative {
-                if filter.is_match(value) {
-                    return Err(FilterError::NegativeMatchFailed);
-                }
-            }
+            Not(AnyOf(negative.iter().collect())).process(value)?;
         }
         if let Some(positive) = &self.positive {
-            for filter in positive {
-                if !filter.is_match(value) {
-                    return Err(FilterError::PositiveFilterFailed);
-                }
+            let positive: Vec<&Regex> = positive.iter().collect();
+            match self.positive_match {
+                PositiveMatch::All => AllOf(positive).process(value)?,
+                PositiveMatch::Any => AnyOf(positive).process(value)?,
             }
         }
+        if let Some(custom) = &self.custom {
+            custom.process(value)?;
+        }
         Ok(())
     }
 }
@@ -81,6 +275,7 @@ impl Filter for Option<EventFilters> {
     }
 }
 
+/// A bare list of regex acts as a negative filter: the value is rejected if it MATCHES any of them.
 impl Filter for Vec<Regex> {
     fn process(&self, value: &str) -> Result<(), FilterError> {
         for filter in self {
@@ -102,13 +297,277 @@ impl Filter for Option<Vec<Regex>> {
     }
 }
 
+/// A per-layer filter deciding which events reach the webhook layer at all.
+///
+/// Unlike the regex-based [`EventFilters`], this filter is evaluated by `tracing-subscriber` when a
+/// callsite is first registered, so events from disabled callsites cost nothing to skip. It
+/// combines an optional level threshold with optional [`EnvFilter`] directives (e.g.
+/// `my_app=warn,billing=info`).
+///
+/// Only events are filtered: spans are always enabled for the layer, so that events which do reach
+/// the webhook still carry the fields of their parent span.
+///
+/// Clones share the same state, so that the filter can be reloaded at runtime through a
+/// [`ReloadHandle`](crate::reload::ReloadHandle).
+#[derive(Debug, Clone, Default)]
+pub struct CallsiteFilter {
+    inner: Arc<RwLock<CallsiteFilterInner>>,
+}
+
+#[derive(Debug, Default)]
+struct CallsiteFilterInner {
+    level: Option<LevelFilter>,
+    directives: Option<EnvFilter>,
+}
+
+impl CallsiteFilter {
+    /// Create a filter from an optional level threshold and optional directives.
+    pub fn new(level: Option<LevelFilter>, directives: Option<EnvFilter>) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(CallsiteFilterInner { level, directives })),
+        }
+    }
+
+    /// Replace the level threshold and directives.
+    ///
+    /// The interest of already registered callsites is cached; callers must rebuild the cache with
+    /// `tracing::callsite::rebuild_interest_cache` for the change to take effect.
+    pub(crate) fn set(&self, level: Option<LevelFilter>, directives: Option<EnvFilter>) {
+        *self.inner.write().unwrap_or_else(|poisoned| poisoned.into_inner()) =
+            CallsiteFilterInner { level, directives };
+    }
+
+    /// Replace only the level threshold, keeping the current directives.
+    pub(crate) fn set_level(&self, level: Option<LevelFilter>) {
+        self.inner.write().unwrap_or_else(|poisoned| poisoned.into_inner()).level = level;
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, CallsiteFilterInner> {
+        self.inner.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl CallsiteFilterInner {
+    fn level_enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.level.is_none_or(|level| metadata.level() <= &level)
+    }
+}
+
+impl<S> layer::Filter<S> for CallsiteFilter {
+    fn enabled(&self, metadata: &Metadata<'_>, ctx: &Context<'_, S>) -> bool {
+        if !metadata.is_event() {
+            return true;
+        }
+        let inner = self.read();
+        inner.level_enabled(metadata)
+            && inner
+                .directives
+                .as_ref()
+                .is_none_or(|directives| layer::Filter::<S>::enabled(directives, metadata, ctx))
+    }
+
+    fn callsite_enabled(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if !metadata.is_event() {
+            return Interest::always();
+        }
+        let inner = self.read();
+        if !inner.level_enabled(metadata) {
+            return Interest::never();
+        }
+        match &inner.directives {
+            Some(directives) => layer::Filter::<S>::callsite_enabled(directives, metadata),
+            None => Interest::always(),
+        }
+    }
+
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(directives) = &self.read().directives {
+            layer::Filter::on_new_span(directives, attrs, id, ctx);
+        }
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        if let Some(directives) = &self.read().directives {
+            layer::Filter::on_record(directives, id, values, ctx);
+        }
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(directives) = &self.read().directives {
+            layer::Filter::on_enter(directives, id, ctx);
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(directives) = &self.read().directives {
+            layer::Filter::on_exit(directives, id, ctx);
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        if let Some(directives) = &self.read().directives {
+            layer::Filter::on_close(directives, id, ctx);
+        }
+    }
+}
+
+/// Identifies which of the layer's filters rejected an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FilterKind {
+    /// The filter applied to the event's target.
+    Target,
+    /// The filter applied to the event's message.
+    Message,
+    /// The filter applied to the keys of the event's fields.
+    EventField,
+    /// The per-callsite rate limit.
+    RateLimit,
+    /// The per-level sampling.
+    Sampling,
+    /// The quiet hours of the schedule.
+    Schedule,
+    /// The callsites muted at runtime, see [`Mutes`].
+    Mute,
+}
+
+impl fmt::Display for FilterKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FilterKind::Target => "target",
+            FilterKind::Message => "message",
+            FilterKind::EventField => "event field",
+            FilterKind::RateLimit => "rate limit",
+            FilterKind::Sampling => "sampling",
+            FilterKind::Schedule => "schedule",
+            FilterKind::Mute => "mute",
+        })
+    }
+}
+
+/// Identify a callsite by its target and location, e.g. to mute it with [`Mutes`].
+///
+/// The id is the same in every build, so that buttons on messages sent before a redeploy still
+/// mute their callsite. It is hashed with FNV-1a, as the output of `DefaultHasher` may change.
+pub fn callsite_id(target: &str, file: &str, line: u32) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    let hash = format!("{}:{}:{}", target, file, line)
+        .bytes()
+        .fold(OFFSET_BASIS, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(PRIME));
+    format!("{:016x}", hash)
+}
+
+/// Callsites muted until a point in time, e.g. from a button on a message, identified by
+/// [`callsite_id`]. Events from a muted callsite are rejected.
+#[derive(Debug, Clone, Default)]
+pub struct Mutes {
+    until: HashMap<String, SystemTime>,
+}
+
+impl Mutes {
+    /// Mute a callsite until the given time, replacing any earlier mute of it. Expired mutes are
+    /// forgotten.
+    pub fn mute(&mut self, callsite: impl Into<String>, until: SystemTime) {
+        let now = SystemTime::now();
+        self.until.retain(|_, muted_until| *muted_until > now);
+        self.until.insert(callsite.into(), until);
+    }
+
+    pub fn unmute(&mut self, callsite: &str) {
+        self.until.remove(callsite);
+    }
+
+    /// Whether the callsite is muted at the given time.
+    pub fn is_muted(&self, callsite: &str, at: SystemTime) -> bool {
+        self.until.get(callsite).is_some_and(|until| *until > at)
+    }
+}
+
+/// An error produced while filtering or formatting an event.
+#[derive(Debug)]
 pub enum FilterError {
+    /// The value did not match the positive filters.
     PositiveFilterFailed,
+    /// The value matched a negative filter.
     NegativeMatchFailed,
+    /// The event's callsite exceeded its rate limit.
+    RateLimited,
+    /// The event was not selected by sampling.
+    Sampled,
+    /// The event fell into quiet hours.
+    QuietHours,
+    /// The event's callsite is muted.
+    Muted,
+    /// One of the layer's filters rejected the event, because of the given value.
+    Rejected {
+        filter: FilterKind,
+        value: String,
+        reason: Box<FilterError>,
+    },
     IoError(Box<dyn std::error::Error>),
     SerdeError(serde_json::Error),
 }
 
+impl FilterError {
+    /// Attribute this error to one of the layer's filters and the value it was applied to.
+    pub fn rejected_by(self, filter: FilterKind, value: &str) -> Self {
+        FilterError::Rejected {
+            filter,
+            value: value.to_string(),
+            reason: Box::new(self),
+        }
+    }
+
+    /// Whether this error means the event was intentionally filtered out, rather than failing to
+    /// be processed.
+    pub fn is_rejection(&self) -> bool {
+        matches!(
+            self,
+            FilterError::PositiveFilterFailed
+                | FilterError::NegativeMatchFailed
+                | FilterError::RateLimited
+                | FilterError::Sampled
+                | FilterError::QuietHours
+                | FilterError::Muted
+                | FilterError::Rejected { .. }
+        )
+    }
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterError::PositiveFilterFailed => f.write_str("value did not match the positive filters"),
+            FilterError::NegativeMatchFailed => f.write_str("value matched a negative filter"),
+            FilterError::RateLimited => f.write_str("callsite exceeded its rate limit"),
+            FilterError::Sampled => f.write_str("event was not selected by sampling"),
+            FilterError::QuietHours => f.write_str("event fell into quiet hours"),
+            FilterError::Muted => f.write_str("callsite is muted"),
+            FilterError::Rejected { filter, value, reason } => {
+                write!(f, "{} filter rejected {:?}: {}", filter, value, reason)
+            }
+            FilterError::IoError(e) => write!(f, "failed to process event: {}", e),
+            FilterError::SerdeError(e) => write!(f, "failed to serialize event metadata: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FilterError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FilterError::Rejected { reason, .. } => Some(reason.as_ref()),
+            FilterError::IoError(e) => Some(e.as_ref()),
+            FilterError::SerdeError(e) => Some(e),
+            FilterError::PositiveFilterFailed
+            | FilterError::NegativeMatchFailed
+            | FilterError::RateLimited
+            | FilterError::Sampled
+            | FilterError::QuietHours
+            | FilterError::Muted => None,
+        }
+    }
+}
+
 impl From<Box<dyn std::error::Error>> for FilterError {
     fn from(e: Box<dyn std::error::Error>) -> Self {
         FilterError::IoError(e)
@@ -120,3 +579,156 @@ impl From<serde_json::Error> for FilterError {
         FilterError::SerdeError(e)
     }
 }
+
+/// A single event dropped by one of the layer's filters.
+#[derive(Debug, Clone)]
+pub struct Rejection {
+    /// The filter which rejected the event.
+    pub filter: FilterKind,
+    /// The target of the rejected event.
+    pub target: String,
+    /// The level of the rejected event.
+    pub level: Level,
+    /// Why the event was rejected.
+    pub reason: String,
+}
+
+/// Records why events were dropped by a layer's filters, for debugging filter configuration.
+///
+/// Recording is opt-in: create a recorder, keep a clone of it, and hand the other to
+/// `WebhookLayerBuilder::record_rejections`. Counts are kept per filter, along with the most
+/// recent rejections up to the configured capacity.
+#[derive(Debug, Clone)]
+pub struct FilterRejections {
+    inner: Arc<Mutex<RejectionsInner>>,
+}
+
+#[derive(Debug)]
+struct RejectionsInner {
+    capacity: usize,
+    counts: HashMap<FilterKind, u64>,
+    recent: VecDeque<Rejection>,
+}
+
+impl FilterRejections {
+    /// Create a recorder which remembers up to `capacity` of the most recent rejections.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(RejectionsInner {
+                capacity,
+                counts: HashMap::new(),
+                recent: VecDeque::with_capacity(capacity),
+            })),
+        }
+    }
+
+    /// The number of events rejected by the given filter so far.
+    pub fn count(&self, filter: FilterKind) -> u64 {
+        self.lock().counts.get(&filter).copied().unwrap_or(0)
+    }
+
+    /// The most recent rejections, oldest first.
+    pub fn recent(&self) -> Vec<Rejection> {
+        self.lock().recent.iter().cloned().collect()
+    }
+
+    /// Forget all recorded rejections.
+    pub fn clear(&self) {
+        let mut inner = self.lock();
+        inner.counts.clear();
+        inner.recent.clear();
+    }
+
+    pub(crate) fn record(&self, target: &str, level: Level, error: &FilterError) {
+        let filter = match error {
+            FilterError::Rejected { filter, .. } => *filter,
+            _ => return,
+        };
+        let mut inner = self.lock();
+        *inner.counts.entry(filter).or_insert(0) += 1;
+        if inner.capacity == 0 {
+            return;
+        }
+        if inner.recent.len() == inner.capacity {
+            inner.recent.pop_front();
+        }
+        inner.recent.push_back(Rejection {
+            filter,
+            target: target.to_string(),
+            level,
+            reason: error.to_string(),
+        });
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RejectionsInner> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn re(expr: &str) -> Regex {
+        Regex::new(expr).unwrap()
+    }
+
+    #[test]
+    fn combinators_express_either_but_not() {
+        let filter = re("^billing").or(re("^payments")).and(re("::internal").not());
+        assert!(filter.process("billing::api").is_ok());
+        assert!(filter.process("payments::api").is_ok());
+        assert!(filter.process("payments::internal").is_err());
+        assert!(filter.process("users::api").is_err());
+    }
+
+    #[test]
+    fn any_of_and_all_of_handle_empty_lists() {
+        assert!(AnyOf(Vec::<Regex>::new()).process("x").is_err());
+        assert!(AllOf(Vec::<Regex>::new()).process("x").is_ok());
+    }
+
+    #[test]
+    fn event_filters_positive_match_modes() {
+        let filters = EventFilters::new(Some(vec![re("^billing"), re("^payments")]), Some(vec![re("secret")]));
+        assert!(filters.process("billing").is_err());
+
+        let filters = filters.match_any();
+        assert!(filters.process("billing").is_ok());
+        assert!(filters.process("payments").is_ok());
+        assert!(filters.process("billing::secret").is_err());
+        assert!(filters.process("users").is_err());
+    }
+
+    #[test]
+    fn event_filters_apply_custom_filter() {
+        let filters = EventFilters::from(re("^app")).with_filter(Not(re("noisy")));
+        assert!(filters.process("app::core").is_ok());
+        assert!(filters.process("app::noisy").is_err());
+    }
+
+    #[test]
+    fn rejections_are_attributed_and_recorded() {
+        let error = re("^app").process("other").unwrap_err().rejected_by(FilterKind::Target, "other");
+        assert!(error.is_rejection());
+        assert_eq!(
+            error.to_string(),
+            "target filter rejected \"other\": value did not match the positive filters"
+        );
+
+        let rejections = FilterRejections::new(1);
+        rejections.record("other", Level::INFO, &error);
+        rejections.record("another", Level::WARN, &error);
+        assert_eq!(rejections.count(FilterKind::Target), 2);
+        assert_eq!(rejections.count(FilterKind::Message), 0);
+        let recent = rejections.recent();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].target, "another");
+    }
+
+    #[test]
+    fn callsite_ids_are_stable() {
+        assert_eq!(callsite_id("billing::api", "src/api.rs", 42), "4f85b4b5b0058988");
+        assert_ne!(callsite_id("billing::api", "src/api.rs", 43), callsite_id("billing::api", "src/api.rs", 42));
+    }
+}
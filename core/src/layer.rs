@@ -1,61 +1,104 @@
-use std::collections::HashMap;
-use std::error::Error;
-use std::future::Future;
-use std::process::Output;
-use std::str::FromStr;
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
 
+use chrono::Utc;
+use debug_print::debug_println;
 use regex::Regex;
 use serde::ser::SerializeMap;
 use serde::Serializer;
 use serde_json::Value;
 use tokio::sync::Mutex;
-use tracing::{Event, Subscriber};
-use tracing::log::LevelFilter;
+use tracing::{Event, Level, Subscriber};
+use tracing::metadata::{LevelFilter, ParseLevelFilterError};
 use tracing_bunyan_formatter::JsonStorage;
-use tracing_subscriber::Layer;
-use tracing_subscriber::layer::Context;
+use tracing_subscriber::{
+    filter::Filtered,
+    layer::{Context, Layer},
+    registry::LookupSpan,
+    EnvFilter,
+};
 
-use crate::{BackgroundWorker, ChannelSender, Config, EventFilters, WebhookMessageFactory, WebhookMessageInputs, WorkerMessage};
-use crate::filters::{Filter, FilterError};
-use crate::worker::worker;
+use crate::{
+    BackgroundWorker,
+    ChannelSender,
+    Config,
+    ConfigurableFactory,
+    EventFilters,
+    MessageFormat,
+    PlatformSettings,
+    WebhookClient,
+    WebhookMessage,
+    WebhookMessageFactory,
+    WebhookMessageInputs,
+    WorkerMessage,
+};
+#[cfg(feature = "aws-lambda")]
+use crate::aws_lambda::LambdaContext;
+use crate::backtrace::BacktraceCapture;
+use crate::error_chain::ErrorChainVisitor;
+use crate::filters::{callsite_id, CallsiteFilter, Filter, FilterError, FilterKind, FilterRejections, Mutes};
+#[cfg(feature = "opentelemetry")]
+use crate::otel::TraceContext;
+use crate::panic::PanicHook;
+use crate::rate_limit::{Limiter, RateLimit, Sampling, Suppressed};
+use crate::thread::{fingerprint, ThreadBy};
+use crate::reload::{LayerFilters, ReloadHandle, SharedLayerFilters};
+use crate::schedule::{DigestEntry, Digests, Schedule, ScheduleAction};
+use crate::worker::ScheduledMessage;
+
+/// The placeholder sent in place of the value of a redacted field.
+pub const REDACTED_FIELD_VALUE: &str = "[REDACTED]";
+
+/// The environment variables checked, in order, for the deployment environment.
+const ENVIRONMENT_VARS: [&str; 4] = ["APP_ENV", "ENVIRONMENT", "ENV", "RUST_ENV"];
+
+/// A [`WebhookLayer`] wrapped in its per-layer [`CallsiteFilter`], as returned by
+/// [`WebhookLayerBuilder::build`].
+pub type FilteredWebhookLayer<C, F, S> = Filtered<WebhookLayer<C, F>, CallsiteFilter, S>;
 
 /// Layer for forwarding tracing events to webhook endpoints.
 pub struct WebhookLayer<C: Config, F: WebhookMessageFactory> {
-    /// Filter events by their target.
-    ///
-    /// Filter type semantics:
-    /// - Subtractive: Exclude an event if the target does NOT MATCH a given regex.
-    /// - Additive: Exclude an event if the target MATCHES a given regex.
-    target_filters: EventFilters,
+    /// The filters and destination of the layer, which may be swapped at runtime.
+    filters: SharedLayerFilters,
 
-    /// Filter events by their message.
-    ///
-    /// Filter type semantics:
-    /// - Positive: Exclude an event if the message MATCHES a given regex, and
-    /// - Negative: Exclude an event if the message does NOT MATCH a given regex.
-    message_filters: Option<EventFilters>,
+    /// The per-layer callsite filter wrapping this layer, shared with reload handles.
+    callsite_filter: CallsiteFilter,
 
-    /// Filter events by fields.
-    ///
-    /// Filter type semantics:
-    /// - Positive: Exclude the event if its key MATCHES a given regex.
-    /// - Negative: Exclude the event if its key does NOT MATCH a given regex.
-    event_by_field_filters: Option<EventFilters>,
+    /// The settings applied to every message, shared with the panic hook and digests.
+    settings: MessageSettings,
 
-    /// Filter fields of events from being sent to the webhook.
-    ///
-    /// Filter type semantics:
-    /// - Positive: Exclude event fields if the field's key MATCHES any provided regular expressions.
-    field_exclusion_filters: Option<Vec<Regex>>,
+    /// Read the channel of each message from this event field, if set.
+    channel_field: Option<String>,
 
-    /// Filter events by their level.
-    level_filter: Option<String>,
+    /// Relate messages to each other, so that platforms may group them into threads.
+    thread_by: Option<ThreadBy>,
 
-    app_name: String,
+    /// The layer's connection to the Webhook API is configured once, when the layer is built.
+    config: std::marker::PhantomData<C>,
 
-    /// Configure the layer's connection to the Webhook API.
-    config: C,
+    /// Record why events were rejected by the filters, if enabled.
+    rejections: Option<FilterRejections>,
+
+    /// Rate limit and sample events per callsite, if enabled.
+    limiter: Limiter,
+
+    /// Suppress, delay or reroute events during quiet hours, if enabled.
+    schedule: Option<Schedule>,
+
+    /// The events held back for digests by the schedule.
+    digests: Digests,
+
+    /// Capture backtraces for severe events, if enabled.
+    backtrace: Option<BacktraceCapture>,
+
+    /// The template of links to an event's trace, if any.
+    #[cfg(feature = "opentelemetry")]
+    trace_url_template: Option<String>,
+
+    /// The Lambda function and invocation the process is handling, if any.
+    #[cfg(feature = "aws-lambda")]
+    lambda: Option<LambdaContext>,
 
     factory: std::marker::PhantomData<F>,
 
@@ -64,6 +107,58 @@ pub struct WebhookLayer<C: Config, F: WebhookMessageFactory> {
     sender: ChannelSender,
 }
 
+/// The settings a layer applies to every message it creates, shared with its panic hook and
+/// digests.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MessageSettings {
+    pub(crate) app_name: String,
+    /// Static context attached to every message.
+    pub(crate) context: BTreeMap<String, String>,
+    /// The layout of messages, if not the platform's default.
+    pub(crate) format: Option<MessageFormat>,
+    /// Whether platforms escape markup in logged text.
+    pub(crate) escape_markup: bool,
+    /// The channel messages are posted to, when sent through a platform's API.
+    pub(crate) channel: Option<String>,
+    /// The template of the runbook link, see [`WebhookLayerBuilder::runbook_url`].
+    pub(crate) runbook_url: Option<String>,
+    /// The settings of the platform's factory.
+    pub(crate) platform: PlatformSettings,
+}
+
+impl MessageSettings {
+    /// The runbook link for events of the given target and level.
+    pub(crate) fn runbook_url(&self, target: &str, level: Level) -> Option<String> {
+        let template = self.runbook_url.as_ref()?;
+        Some(template.replace("{target}", target).replace("{level}", level.as_str()))
+    }
+
+    /// The inputs of a message about an event of the given target and level, with these settings
+    /// applied. Details of the event, such as its source, span and fields, are left empty.
+    pub(crate) fn inputs(
+        &self,
+        message: String,
+        target: &str,
+        level: Level,
+        webhook_url: String,
+    ) -> WebhookMessageInputs {
+        WebhookMessageInputs {
+            app_name: self.app_name.clone(),
+            message,
+            target: target.to_string(),
+            webhook_url,
+            event_level: level,
+            context: self.context.clone(),
+            format: self.format,
+            escape_markup: self.escape_markup,
+            channel: self.channel.clone(),
+            runbook_url: self.runbook_url(target, level),
+            platform: self.platform.clone(),
+            ..Default::default()
+        }
+    }
+}
+
 impl<C: Config, F: WebhookMessageFactory> WebhookLayer<C, F> {
     /// Create a new layer for forwarding messages to the webhook, using a specified
     /// configuration. The background worker must be started in order to spawn spawns
@@ -73,23 +168,44 @@ impl<C: Config, F: WebhookMessageFactory> WebhookLayer<C, F> {
     /// used to shutdown the background worker, and a future to spawn as a task on a tokio runtime
     /// to initialize the worker's processing and sending of HTTP requests to the webhook.
     pub(crate) fn new(
-        app_name: String,
-        target_filters: EventFilters,
-        message_filters: Option<EventFilters>,
-        event_by_field_filters: Option<EventFilters>,
-        field_exclusion_filters: Option<Vec<Regex>>,
-        level_filter: Option<String>,
-        config: C,
+        builder: WebhookLayerBuilder<C, F>,
+        callsite_filter: CallsiteFilter,
     ) -> (WebhookLayer<C, F>, BackgroundWorker) {
         let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let config = builder.config.unwrap_or_else(C::new_from_env);
+        let filters = LayerFilters {
+            target_filters: builder.target_filters,
+            message_filters: builder.message_filters,
+            event_by_field_filters: builder.event_by_field_filters,
+            field_exclusion_filters: builder.field_exclusion_filters,
+            field_redaction_filters: builder.field_redaction_filters,
+            webhook_url: config.webhook_url().to_string(),
+            mutes: Mutes::default(),
+        };
         let layer = WebhookLayer {
-            target_filters,
-            message_filters,
-            field_exclusion_filters,
-            event_by_field_filters,
-            level_filter,
-            app_name,
-            config,
+            filters: Arc::new(RwLock::new(Arc::new(filters))),
+            callsite_filter,
+            settings: MessageSettings {
+                app_name: builder.app_name,
+                context: builder.context,
+                format: builder.format,
+                escape_markup: builder.escape_markup,
+                channel: config.channel().map(str::to_string),
+                runbook_url: builder.runbook_url,
+                platform: builder.platform,
+            },
+            channel_field: builder.channel_field,
+            thread_by: builder.thread_by,
+            config: Default::default(),
+            rejections: builder.rejections,
+            limiter: Limiter::new(builder.rate_limit, builder.sampling),
+            schedule: builder.schedule,
+            digests: Digests::default(),
+            backtrace: builder.backtrace,
+            #[cfg(feature = "opentelemetry")]
+            trace_url_template: builder.trace_url_template,
+            #[cfg(feature = "aws-lambda")]
+            lambda: builder.lambda,
             factory: Default::default(),
             sender: tx.clone(),
         };
@@ -97,10 +213,33 @@ impl<C: Config, F: WebhookMessageFactory> WebhookLayer<C, F> {
             sender: tx,
             handle: Arc::new(Mutex::new(None)),
             rx: Arc::new(Mutex::new(rx)),
+            client: WebhookClient::new(config.api_url(), &config.headers()),
         };
         (layer, background_worker)
     }
 
+    /// Create a handle for reloading the filters, level threshold and destination of this layer
+    /// while it is running.
+    ///
+    /// The layer returned by [`WebhookLayerBuilder::build`] is wrapped in its callsite filter; use
+    /// `layer.inner().reload_handle()` to obtain a handle before installing it.
+    pub fn reload_handle(&self) -> ReloadHandle {
+        ReloadHandle::new(self.filters.clone(), self.callsite_filter.clone())
+    }
+
+    /// Create a panic hook reporting panics through this layer's worker, to the layer's current
+    /// webhook URL. The hook captures backtraces if the layer does.
+    ///
+    /// The layer returned by [`WebhookLayerBuilder::build`] is wrapped in its callsite filter; use
+    /// `layer.inner().panic_hook().install()` to install the hook.
+    pub fn panic_hook(&self) -> PanicHook
+    where
+        F: 'static,
+    {
+        PanicHook::new(self.settings.clone(), self.filters.clone(), self.sender.clone(), create_boxed::<F>)
+            .with_backtrace(self.backtrace.clone())
+    }
+
     /// Create a new builder for the webhook layer.
     pub fn builder(app_name: String, target_filters: EventFilters) -> WebhookLayerBuilder<C, F> {
         WebhookLayerBuilder::new(app_name, target_filters)
@@ -121,8 +260,26 @@ pub struct WebhookLayerBuilder<C: Config, F: WebhookMessageFactory> {
     message_filters: Option<EventFilters>,
     event_by_field_filters: Option<EventFilters>,
     field_exclusion_filters: Option<Vec<Regex>>,
-    level_filters: Option<String>,
+    field_redaction_filters: Option<Vec<Regex>>,
+    level_filter: Option<LevelFilter>,
+    directives: Option<EnvFilter>,
     config: Option<C>,
+    rejections: Option<FilterRejections>,
+    rate_limit: Option<RateLimit>,
+    sampling: Option<Sampling>,
+    schedule: Option<Schedule>,
+    backtrace: Option<BacktraceCapture>,
+    context: BTreeMap<String, String>,
+    format: Option<MessageFormat>,
+    escape_markup: bool,
+    channel_field: Option<String>,
+    thread_by: Option<ThreadBy>,
+    runbook_url: Option<String>,
+    platform: PlatformSettings,
+    #[cfg(feature = "opentelemetry")]
+    trace_url_template: Option<String>,
+    #[cfg(feature = "aws-lambda")]
+    lambda: Option<LambdaContext>,
 }
 
 impl<C: Config, F: WebhookMessageFactory> WebhookLayerBuilder<C, F> {
@@ -134,8 +291,26 @@ impl<C: Config, F: WebhookMessageFactory> WebhookLayerBuilder<C, F> {
             message_filters: None,
             event_by_field_filters: None,
             field_exclusion_filters: None,
-            level_filters: None,
+            field_redaction_filters: None,
+            level_filter: None,
+            directives: None,
             config: None,
+            rejections: None,
+            rate_limit: None,
+            sampling: None,
+            schedule: None,
+            backtrace: None,
+            context: BTreeMap::new(),
+            format: None,
+            escape_markup: true,
+            channel_field: None,
+            thread_by: None,
+            runbook_url: None,
+            platform: PlatformSettings::default(),
+            #[cfg(feature = "opentelemetry")]
+            trace_url_template: None,
+            #[cfg(feature = "aws-lambda")]
+            lambda: None,
         }
     }
 
@@ -168,6 +343,16 @@ impl<C: Config, F: WebhookMessageFactory> WebhookLayerBuilder<C, F> {
         self
     }
 
+    /// Redact the values of fields sent to the webhook.
+    ///
+    /// Filter type semantics:
+    /// - Positive: Replace the value of event and span fields with a placeholder if the field's key
+    ///   MATCHES any provided regular expressions.
+    pub fn field_redaction_filters(mut self, filters: Vec<Regex>) -> Self {
+        self.field_redaction_filters = Some(filters);
+        self
+    }
+
     /// Configure the layer's connection to the webhook.
     pub fn config(mut self, config: C) -> Self {
         self.config = Some(config);
@@ -175,41 +360,245 @@ impl<C: Config, F: WebhookMessageFactory> WebhookLayerBuilder<C, F> {
     }
 
     /// Configure which levels of events to send to the webhook.
-    pub fn level_filters(mut self, level_filters: String) -> Self {
-        self.level_filters = Some(level_filters);
+    ///
+    /// An invalid level is reported and ignored; use [`try_level_filters`](Self::try_level_filters)
+    /// to handle it instead.
+    pub fn level_filters(self, level_filters: String) -> Self {
+        match level_filters.parse() {
+            Ok(level_filter) => self.level_filter(level_filter),
+            Err(e) => {
+                println!("ERROR: ignoring invalid level filter {:?}: {}", level_filters, e);
+                self
+            }
+        }
+    }
+
+    /// Configure which levels of events to send to the webhook, failing if the given string is not
+    /// a valid level, such as `"warn"`.
+    pub fn try_level_filters(self, level_filters: &str) -> Result<Self, ParseLevelFilterError> {
+        Ok(self.level_filter(level_filters.parse()?))
+    }
+
+    /// Configure the most verbose level of events to send to the webhook.
+    ///
+    /// Events more verbose than this level are disabled at their callsite.
+    pub fn level_filter(mut self, level_filter: LevelFilter) -> Self {
+        self.level_filter = Some(level_filter);
+        self
+    }
+
+    /// Only send events enabled by the given directives, e.g. `"my_app=warn,billing=info"`.
+    ///
+    /// The directives use the [`EnvFilter`] syntax and are evaluated per callsite, before any of the
+    /// regex-based filters. They apply to this layer only, and do not affect other layers.
+    pub fn filter(mut self, directives: impl Into<EnvFilter>) -> Self {
+        self.directives = Some(directives.into());
+        self
+    }
+
+    /// Record why events were rejected by the layer's filters into the given recorder.
+    ///
+    /// Keep a clone of the recorder to inspect rejection counts and the most recent rejections,
+    /// e.g. to answer why an event never reached the webhook.
+    pub fn record_rejections(mut self, rejections: FilterRejections) -> Self {
+        self.rejections = Some(rejections);
+        self
+    }
+
+    /// Limit the number of events sent from each callsite, using a token bucket.
+    ///
+    /// Events over the limit are dropped, and the number dropped is reported with the next event
+    /// from the same callsite which gets through.
+    pub fn rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    /// Send only a random sample of the events at each level.
+    ///
+    /// Like rate limiting, the number of events dropped is reported with the next event from the
+    /// same callsite which gets through.
+    pub fn sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = Some(sampling);
+        self
+    }
+
+    /// Apply quiet hours and schedule-based routing to events.
+    ///
+    /// During the schedule's windows, events are suppressed, held back for a digest sent when the
+    /// window ends, or rerouted to another webhook.
+    pub fn schedule(mut self, schedule: Schedule) -> Self {
+        self.schedule = Some(schedule);
+        self
+    }
+
+    /// Attach a backtrace of the code which emitted the event to severe events, trimmed to the
+    /// application's crates.
+    pub fn capture_backtraces(mut self, capture: BacktraceCapture) -> Self {
+        self.backtrace = Some(capture);
+        self
+    }
+
+    /// Lay messages out in the given format, rather than the platform's default.
+    pub fn format(mut self, format: MessageFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Escape markup in logged text, such as messages, field values and error sources, so that it
+    /// is shown as logged and cannot mention everyone in a channel. Enabled by default; disable it
+    /// to let deliberately logged markup render.
+    pub fn escape_markup(mut self, escape_markup: bool) -> Self {
+        self.escape_markup = escape_markup;
+        self
+    }
+
+    /// Post each message to the channel named by this event field, if the event has it, rather
+    /// than the configured channel. Only applies when sending through a platform's API, see
+    /// [`Config::channel`].
+    pub fn channel_field(mut self, field: impl Into<String>) -> Self {
+        self.channel_field = Some(field.into());
+        self
+    }
+
+    /// Post related events as replies in the thread of the first one, on platforms which support
+    /// threads, such as Slack through its Web API.
+    pub fn thread_by(mut self, thread_by: ThreadBy) -> Self {
+        self.thread_by = Some(thread_by);
+        self
+    }
+
+    /// Link each message to a runbook, e.g. `https://wiki.example.com/runbooks/{target}`, where
+    /// `{target}` and `{level}` are replaced by the event's target and level. Slack shows a button
+    /// and Discord links the embed's title.
+    pub fn runbook_url(mut self, template: impl Into<String>) -> Self {
+        self.runbook_url = Some(template.into());
+        self
+    }
+
+    /// Attach a static `key: value` pair of context to every message, e.g. the region the
+    /// application runs in.
+    pub fn context(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.context.insert(key.into(), value.into());
+        self
+    }
+
+    /// Attach the value of an environment variable to every message as context, if it is set.
+    pub fn context_from_env(self, key: impl Into<String>, var: &str) -> Self {
+        match std::env::var(var) {
+            Ok(value) if !value.is_empty() => self.context(key, value),
+            _ => self,
+        }
+    }
+
+    /// Attach the host name to every message as `host`, taken from the `HOSTNAME` environment
+    /// variable or, failing that, `/etc/hostname`.
+    pub fn hostname(self) -> Self {
+        let hostname = std::env::var("HOSTNAME")
+            .ok()
+            .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+            .map(|hostname| hostname.trim().to_string())
+            .filter(|hostname| !hostname.is_empty());
+        match hostname {
+            Some(hostname) => self.context("host", hostname),
+            None => self,
+        }
+    }
+
+    /// Attach the application's version to every message as `version`. Pass
+    /// `env!("CARGO_PKG_VERSION")` to use the version of the application's package.
+    pub fn version(self, version: impl Into<String>) -> Self {
+        self.context("version", version)
+    }
+
+    /// Attach the deployment environment (e.g. prod or staging) to every message as `env`, taken
+    /// from the first of the `APP_ENV`, `ENVIRONMENT`, `ENV` and `RUST_ENV` environment variables
+    /// which is set.
+    pub fn environment_from_env(self) -> Self {
+        match ENVIRONMENT_VARS.iter().find_map(|var| std::env::var(var).ok().filter(|value| !value.is_empty())) {
+            Some(environment) => self.context("env", environment),
+            None => self,
+        }
+    }
+
+    /// Link the OpenTelemetry trace of each event to a trace viewer, using a URL template in which
+    /// `{trace_id}` and `{span_id}` are replaced, e.g.
+    /// `"https://jaeger.example.com/trace/{trace_id}?uiFind={span_id}"`.
+    ///
+    /// Trace and span ids are included in messages whenever the subscriber includes a
+    /// `tracing_opentelemetry::OpenTelemetryLayer`, with or without a template.
+    #[cfg(feature = "opentelemetry")]
+    pub fn trace_url_template(mut self, template: impl Into<String>) -> Self {
+        self.trace_url_template = Some(template.into());
+        self
+    }
+
+    /// Attach the Lambda function, the current invocation's request id and a link to its logs to
+    /// every message. Pass `LambdaContext::from_env()`, which is `None` outside of Lambda, and give
+    /// the same context to the [`LambdaExtension`](crate::aws_lambda::LambdaExtension) to track
+    /// request ids.
+    #[cfg(feature = "aws-lambda")]
+    pub fn lambda_context(mut self, context: Option<LambdaContext>) -> Self {
+        self.lambda = context;
         self
     }
 
     /// Create a webhook layer and its corresponding background worker to (async) send the messages.
-    pub fn build(self) -> (WebhookLayer<C, F>, BackgroundWorker) {
-        WebhookLayer::new(
-            self.app_name,
-            self.target_filters,
-            self.message_filters,
-            self.event_by_field_filters,
-            self.field_exclusion_filters,
-            self.level_filters,
-            self.config.unwrap_or_else(C::new_from_env),
-        )
+    ///
+    /// The layer is returned wrapped in its per-layer [`CallsiteFilter`], built from the configured
+    /// level threshold and directives.
+    pub fn build<S>(mut self) -> (FilteredWebhookLayer<C, F, S>, BackgroundWorker)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let filter = CallsiteFilter::new(self.level_filter.take(), self.directives.take());
+        let (layer, background_worker) = WebhookLayer::new(self, filter.clone());
+        (Filtered::new(layer, filter), background_worker)
+    }
+}
+
+impl<C: Config, F: ConfigurableFactory> WebhookLayerBuilder<C, F> {
+    /// Configure the platform's own settings for its messages, e.g. a `SlackSettings` for Slack.
+    pub fn platform_settings(mut self, settings: F::Settings) -> Self {
+        self.platform = PlatformSettings::new(settings);
+        self
     }
 }
 
+fn create_boxed<F: WebhookMessageFactory + 'static>(inputs: WebhookMessageInputs) -> Box<dyn WebhookMessage> {
+    Box::new(F::create(inputs))
+}
+
 impl<S, C, F> Layer<S> for WebhookLayer<C, F>
 where
-    S: Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    S: Subscriber + for<'a> LookupSpan<'a>,
     C: Config+ 'static,
     F: WebhookMessageFactory + 'static,
 {
     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
-        let current_span = ctx.lookup_current();
-        let mut event_visitor = JsonStorage::default();
-        event.record(&mut event_visitor);
-
+        let filters = self.filters.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
         let format = || {
             const KEYWORDS: [&str; 2] = ["message", "error"];
 
+            // Check the target before doing any work to record the event's fields.
             let target = event.metadata().target();
-            self.target_filters.process(target)?;
+            filters
+                .target_filters
+                .process(target)
+                .map_err(|e| e.rejected_by(FilterKind::Target, target))?;
+
+            let callsite = callsite_id(
+                target,
+                event.metadata().file().unwrap_or("Unknown"),
+                event.metadata().line().unwrap_or(0),
+            );
+            if filters.mutes.is_muted(&callsite, SystemTime::now()) {
+                return Err(FilterError::Muted.rejected_by(FilterKind::Mute, target));
+            }
+
+            let current_span = ctx.lookup_current();
+            let mut event_visitor = JsonStorage::default();
+            event.record(&mut event_visitor);
 
             // Extract the "message" field, if provided. Fallback to the target, if missing.
             let message = event_visitor
@@ -227,39 +616,95 @@ where
                 })
                 .unwrap_or("No message");
 
-            self.message_filters.process(message)?;
-            if let Some(level_filters) = &self.level_filter {
-                let message_level = {
-                    LevelFilter::from_str(event.metadata().level().as_str())
-                        .map_err(|e| FilterError::IoError(Box::new(e)))?
-                };
-                let level_threshold =
-                    LevelFilter::from_str(level_filters).map_err(|e| FilterError::IoError(Box::new(e)))?;
-                if message_level > level_threshold {
-                    return Err(FilterError::PositiveFilterFailed);
+            filters
+                .message_filters
+                .process(message)
+                .map_err(|e| e.rejected_by(FilterKind::Message, message))?;
+
+            // Check the keys of the fields and the rate limit before serializing the fields.
+            let fields = || {
+                event_visitor
+                    .values()
+                    .iter()
+                    .filter(|(&key, _)| !KEYWORDS.contains(&key))
+                    .filter(|(&key, _)| filters.field_exclusion_filters.process(key).is_ok())
+            };
+            for (key, _) in fields() {
+                filters
+                    .event_by_field_filters
+                    .process(key)
+                    .map_err(|e| e.rejected_by(FilterKind::EventField, key))?;
+            }
+            // Quiet hours come first, so that the events they drop or hold back neither use up the
+            // rate limit nor take its count of suppressed events with them.
+            let mut webhook_url = filters.webhook_url.clone();
+            let level = *event.metadata().level();
+            if let Some((window, action, until)) =
+                self.schedule.as_ref().and_then(|schedule| schedule.action_for(level, Utc::now()))
+            {
+                match action {
+                    ScheduleAction::Suppress => {
+                        return Err(FilterError::QuietHours.rejected_by(FilterKind::Schedule, target));
+                    }
+                    ScheduleAction::Reroute(rerouted) => webhook_url = rerouted.clone(),
+                    ScheduleAction::Digest => {
+                        let entry = DigestEntry {
+                            time: Utc::now(),
+                            level,
+                            target: target.to_string(),
+                            message: message.to_string(),
+                        };
+                        if self.digests.hold(window, &webhook_url, entry) {
+                            let digests = self.digests.clone();
+                            let settings = self.settings.clone();
+                            let digest = ScheduledMessage::new(until, move || {
+                                digests.take(window, &settings, create_boxed::<F>)
+                            });
+                            if let Err(e) = self.sender.send(WorkerMessage::Scheduled(digest)) {
+                                println!("failed to schedule webhook digest, err = {}", e)
+                            }
+                        }
+                        return Ok(None);
+                    }
                 }
             }
 
+            let suppressed_events = if self.limiter.is_enabled() {
+                self.limiter
+                    .check(event.metadata(), event_visitor.values())
+                    .map_err(|suppressed| match suppressed {
+                        Suppressed::RateLimited => FilterError::RateLimited.rejected_by(FilterKind::RateLimit, target),
+                        Suppressed::Sampled => FilterError::Sampled.rejected_by(FilterKind::Sampling, target),
+                    })?
+            } else {
+                0
+            };
+            let mut error_visitor = ErrorChainVisitor::default();
+            event.record(&mut error_visitor);
+
+            let redacted = Value::String(REDACTED_FIELD_VALUE.to_string());
+            let redact = |key: &str, value| {
+                if filters.field_redaction_filters.process(key).is_err() {
+                    &redacted
+                } else {
+                    value
+                }
+            };
+
             let mut metadata_buffer = Vec::new();
             let mut serializer = serde_json::Serializer::new(&mut metadata_buffer);
             let mut map_serializer = serializer.serialize_map(None)?;
             // Add all the other fields associated with the event, expect the message we
             // already used.
-            for (key, value) in event_visitor
-                .values()
-                .iter()
-                .filter(|(&key, _)| !KEYWORDS.contains(&key))
-                .filter(|(&key, _)| self.field_exclusion_filters.process(key).is_ok())
-            {
-                self.event_by_field_filters.process(key)?;
-                map_serializer.serialize_entry(key, value)?;
+            for (key, value) in fields() {
+                map_serializer.serialize_entry(key, redact(key, value))?;
             }
             // Add all the fields from the current span, if we have one.
             if let Some(span) = &current_span {
                 let extensions = span.extensions();
                 if let Some(visitor) = extensions.get::<JsonStorage>() {
                     for (key, value) in visitor.values() {
-                        map_serializer.serialize_entry(key, value)?;
+                        map_serializer.serialize_entry(key, redact(key, value))?;
                     }
                 }
             }
@@ -270,29 +715,468 @@ where
                 None => "",
             };
 
+            #[cfg(feature = "opentelemetry")]
+            let trace = current_span
+                .as_ref()
+                .and_then(|span| TraceContext::from_span(span, self.trace_url_template.as_deref()));
+            #[cfg(not(feature = "opentelemetry"))]
+            let trace = None;
+            #[cfg(feature = "aws-lambda")]
+            let lambda = self.lambda.as_ref().map(LambdaContext::invocation);
+            #[cfg(not(feature = "aws-lambda"))]
+            let lambda = None;
+
+            let channel = self
+                .channel_field
+                .as_deref()
+                .and_then(|field| event_visitor.values().get(field))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .or_else(|| self.settings.channel.clone());
+
+            // Look a field up on the event first, then on its spans from the innermost outwards.
+            let field_value = |field: &str| {
+                event_visitor
+                    .values()
+                    .get(field)
+                    .cloned()
+                    .or_else(|| {
+                        current_span.as_ref()?.scope().find_map(|span| {
+                            let extensions = span.extensions();
+                            extensions.get::<JsonStorage>()?.values().get(field).cloned()
+                        })
+                    })
+                    .map(|value| match value {
+                        Value::String(s) => s,
+                        other => other.to_string(),
+                    })
+            };
+            let thread_key = match &self.thread_by {
+                Some(ThreadBy::Field(field)) => field_value(field),
+                Some(ThreadBy::Fingerprint) => Some(fingerprint(
+                    target,
+                    event.metadata().file().unwrap_or("Unknown"),
+                    event.metadata().line().unwrap_or(0),
+                    message,
+                )),
+                None => None,
+            };
+
             let metadata = {
                 let data: HashMap<String, Value> = serde_json::from_slice(metadata_buffer.as_slice()).unwrap();
                 serde_json::to_string_pretty(&data).unwrap()
             };
 
-            Ok(F::create(WebhookMessageInputs {
-                app_name: self.app_name.clone(),
-                message: message.to_string(),
-                event_level: *event.metadata().level(),
+            Ok(Some(F::create(WebhookMessageInputs {
                 source_file: event.metadata().file().unwrap_or("Unknown").to_string(),
                 source_line: event.metadata().line().unwrap_or(0),
-                target: target.to_string(),
                 span: span.to_string(),
                 metadata,
-                webhook_url: self.config.webhook_url().to_string(),
-            }))
+                suppressed_events,
+                errors: error_visitor.into_errors(),
+                backtrace: self.backtrace.as_ref().and_then(|capture| capture.capture(level)),
+                trace,
+                lambda,
+                channel,
+                thread_key,
+                callsite,
+                ..self.settings.inputs(message.to_string(), target, level, webhook_url)
+            })))
         };
 
         let result: Result<_, FilterError> = format();
-        if let Ok(formatted) = result {
-            if let Err(e) = self.sender.send(WorkerMessage::Data(Box::new(formatted))) {
-                println!("failed to send webhook payload to given channel, err = {}", e)
+        match result {
+            Ok(None) => {
+                debug_println!("webhook layer held event back for a digest");
+            }
+            Ok(Some(formatted)) => {
+                if let Err(e) = self.sender.send(WorkerMessage::Data(Box::new(formatted))) {
+                    println!("failed to send webhook payload to given channel, err = {}", e)
+                };
+            }
+            Err(e) if e.is_rejection() => {
+                debug_println!("webhook layer dropped event: {}", e);
+                if let Some(rejections) = &self.rejections {
+                    rejections.record(event.metadata().target(), *event.metadata().level(), &e);
+                }
+            }
+            Err(e) => {
+                println!("ERROR: failed to format webhook message: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::FromWebhookUrl;
+
+    #[derive(Debug)]
+    struct TestMessage(Value);
+
+    impl WebhookMessage for TestMessage {
+        fn webhook_url(&self) -> &str {
+            "http://localhost"
+        }
+
+        fn serialize(&self) -> String {
+            self.0.to_string()
+        }
+    }
+
+    struct TestConfig;
+
+    impl Config for TestConfig {
+        fn webhook_url(&self) -> &str {
+            "http://localhost"
+        }
+
+        fn new_from_env() -> Self {
+            TestConfig
+        }
+    }
+
+    impl FromWebhookUrl for TestConfig {
+        fn new_from_webhook_url(_: String) -> Self {
+            TestConfig
+        }
+    }
+
+    /// Creates messages holding the inputs the tests check.
+    struct TestFactory;
+
+    impl WebhookMessageFactory for TestFactory {
+        fn create(inputs: WebhookMessageInputs) -> impl WebhookMessage {
+            let errors: Vec<String> = inputs
+                .errors
+                .iter()
+                .map(|error| format!("{}={}: {}", error.field, error.message, error.sources.join(" <- ")))
+                .collect();
+            TestMessage(serde_json::json!({
+                "message": inputs.message,
+                "metadata": inputs.metadata,
+                "webhook_url": inputs.webhook_url,
+                "suppressed_events": inputs.suppressed_events,
+                "errors": errors,
+                "callsite": inputs.callsite,
+                "trace_url": inputs.trace.and_then(|trace| trace.url),
+            }))
+        }
+    }
+
+    /// The inputs of the messages sent to the worker so far, as created by [`TestFactory`].
+    fn received(worker: &BackgroundWorker) -> Vec<Value> {
+        let mut rx = worker.rx.try_lock().unwrap();
+        let mut messages = Vec::new();
+        while let Ok(message) = rx.try_recv() {
+            let payload = match message {
+                WorkerMessage::Data(payload) => Some(payload),
+                WorkerMessage::Scheduled(scheduled) => (scheduled.build)(),
+                WorkerMessage::Flush(_) | WorkerMessage::Shutdown => None,
             };
+            if let Some(payload) = payload {
+                messages.push(serde_json::from_str(&WebhookMessage::serialize(payload.as_ref())).unwrap());
+            }
         }
+        messages
+    }
+
+    fn messages(received: &[Value]) -> Vec<&str> {
+        received.iter().map(|inputs| inputs["message"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn callsite_filter_applies_level_and_directives() {
+        let (layer, worker) = WebhookLayer::<TestConfig, TestFactory>::builder("test".into(), Default::default())
+            .level_filter(LevelFilter::INFO)
+            .filter("billing=info,payments=warn")
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "billing", "billing info");
+            tracing::debug!(target: "billing", "billing debug");
+            tracing::info!(target: "payments", "payments info");
+            tracing::warn!(target: "payments", "payments warn");
+            tracing::error!(target: "users", "users error");
+        });
+
+        assert_eq!(messages(&received(&worker)), vec!["billing info", "payments warn"]);
+    }
+
+    #[test]
+    fn invalid_level_filters_are_ignored() {
+        let builder = WebhookLayer::<TestConfig, TestFactory>::builder("test".into(), Default::default());
+        assert_eq!(builder.level_filters("loud".to_string()).level_filter, None);
+
+        let builder = WebhookLayer::<TestConfig, TestFactory>::builder("test".into(), Default::default());
+        assert!(builder.try_level_filters("loud").is_err());
+
+        let builder = WebhookLayer::<TestConfig, TestFactory>::builder("test".into(), Default::default());
+        assert_eq!(builder.try_level_filters("warn").unwrap().level_filter, Some(LevelFilter::WARN));
+    }
+
+    #[test]
+    fn redacted_fields_are_replaced() {
+        let config = crate::config::LayerConfig::from_json_str(
+            r#"{ "app_name": "test", "redact_fields": ["token"], "exclude_fields": ["^internal$"] }"#,
+        )
+        .unwrap();
+        let (layer, worker) = WebhookLayerBuilder::<TestConfig, TestFactory>::from_config(&config)
+            .unwrap()
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(api_token = "secret", internal = 1, user = "alice", "hello");
+        });
+
+        let metadata = received(&worker)[0]["metadata"].as_str().unwrap().to_string();
+        let metadata: HashMap<String, Value> = serde_json::from_str(&metadata).unwrap();
+        assert_eq!(metadata["api_token"], REDACTED_FIELD_VALUE);
+        assert_eq!(metadata["user"], "alice");
+        assert!(!metadata.contains_key("internal"));
+    }
+
+    #[test]
+    fn error_source_chains_are_captured() {
+        #[derive(Debug)]
+        struct Failure(&'static str, Option<Box<Failure>>);
+
+        impl std::fmt::Display for Failure {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.0)
+            }
+        }
+
+        impl std::error::Error for Failure {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                self.1.as_deref().map(|source| source as &(dyn std::error::Error + 'static))
+            }
+        }
+
+        let (layer, worker) =
+            WebhookLayer::<TestConfig, TestFactory>::builder("test".into(), Default::default()).build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let err = Failure(
+                "failed to load user",
+                Some(Box::new(Failure("query failed", Some(Box::new(Failure("connection reset", None)))))),
+            );
+            let cause = Failure("retry budget exhausted", None);
+            tracing::error!(cause = &cause as &dyn std::error::Error, error = &err as &dyn std::error::Error);
+        });
+
+        let received = received(&worker);
+        assert_eq!(messages(&received), vec!["failed to load user"]);
+        assert_eq!(
+            received[0]["errors"],
+            serde_json::json!([
+                "error=failed to load user: query failed <- connection reset",
+                "cause=retry budget exhausted: ",
+            ])
+        );
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    #[test]
+    fn opentelemetry_traces_are_linked() {
+        use opentelemetry::trace::TracerProvider;
+
+        let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder().build();
+        let (layer, worker) = WebhookLayer::<TestConfig, TestFactory>::builder("test".into(), Default::default())
+            .trace_url_template("https://traces.example.com/{trace_id}/{span_id}")
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")))
+            .with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request").in_scope(|| tracing::warn!("inside"));
+            tracing::warn!("outside");
+        });
+
+        let received = received(&worker);
+        let url = Regex::new("^https://traces.example.com/[0-9a-f]{32}/[0-9a-f]{16}$").unwrap();
+        let trace_url = received[0]["trace_url"].as_str().unwrap();
+        assert!(url.is_match(trace_url), "{}", trace_url);
+        assert_eq!(received[1]["trace_url"], Value::Null);
+    }
+
+    #[test]
+    fn reload_handle_swaps_filters_and_level() {
+        let (layer, worker) =
+            WebhookLayer::<TestConfig, TestFactory>::builder("test".into(), Regex::new("^first").unwrap().into())
+                .build();
+        let handle = layer.inner().reload_handle();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let log = |n: u32| {
+                tracing::warn!(target: "first", "first {}", n);
+                tracing::warn!(target: "second", "second {}", n);
+            };
+            log(1);
+
+            let config = crate::config::LayerConfig::from_json_str(
+                r#"{
+                    "app_name": "test",
+                    "level": "error",
+                    "destination": { "webhook_url": "http://localhost/reloaded" },
+                    "targets": { "include": ["^second"] }
+                }"#,
+            )
+            .unwrap();
+            handle.reload_config(&config).unwrap();
+            assert_eq!(handle.current().webhook_url, "http://localhost/reloaded");
+            log(2);
+
+            handle.reload_level(None);
+            log(3);
+        });
+
+        assert_eq!(messages(&received(&worker)), vec!["first 1", "second 3"]);
+    }
+
+    #[test]
+    fn muted_callsites_are_rejected_until_unmuted() {
+        let (layer, worker) =
+            WebhookLayer::<TestConfig, TestFactory>::builder("test".into(), EventFilters::default()).build();
+        let handle = layer.inner().reload_handle();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        let received = tracing::subscriber::with_default(subscriber, || {
+            let log = |n: u32| {
+                tracing::warn!("muted {}", n);
+                tracing::warn!("kept {}", n);
+            };
+            log(1);
+            let callsite = received(&worker)[0]["callsite"].as_str().unwrap().to_string();
+
+            handle.mute_callsite(&callsite, std::time::Duration::from_secs(3600));
+            log(2);
+            let config = crate::config::LayerConfig::from_json_str(r#"{ "app_name": "test" }"#).unwrap();
+            handle.reload_config(&config).unwrap();
+            log(3);
+            handle.unmute_callsite(&callsite);
+            log(4);
+            received(&worker)
+        });
+
+        assert_eq!(messages(&received), vec!["kept 2", "kept 3", "muted 4", "kept 4"]);
+    }
+
+    #[test]
+    fn rate_limited_events_are_counted_in_the_next_message() {
+        let rejections = FilterRejections::new(0);
+        let (layer, worker) = WebhookLayer::<TestConfig, TestFactory>::builder("test".into(), Default::default())
+            .rate_limit(RateLimit::new(1, std::time::Duration::from_millis(50)))
+            .record_rejections(rejections.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let log = || tracing::warn!("hot loop");
+            for _ in 0..5 {
+                log();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(60));
+            log();
+        });
+
+        let suppressed: Vec<Value> = received(&worker)
+            .into_iter()
+            .map(|inputs| inputs["suppressed_events"].clone())
+            .collect();
+        assert_eq!(suppressed, vec![0, 4]);
+        assert_eq!(rejections.count(FilterKind::RateLimit), 4);
+    }
+
+    #[test]
+    fn quiet_hours_apply_before_the_rate_limit() {
+        use chrono::NaiveTime;
+
+        use crate::schedule::QuietWindow;
+
+        let rejections = FilterRejections::new(0);
+        let schedule = Schedule::new(chrono_tz::UTC).window(
+            QuietWindow::new(NaiveTime::MIN, NaiveTime::MIN).up_to(tracing::Level::WARN),
+            ScheduleAction::Suppress,
+        );
+        let (layer, _worker) = WebhookLayer::<TestConfig, TestFactory>::builder("test".into(), Default::default())
+            .rate_limit(RateLimit::new(1, std::time::Duration::from_secs(60)))
+            .schedule(schedule)
+            .record_rejections(rejections.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..3 {
+                tracing::warn!("quiet");
+            }
+        });
+
+        assert_eq!(rejections.count(FilterKind::Schedule), 3);
+        assert_eq!(rejections.count(FilterKind::RateLimit), 0);
+    }
+
+    #[test]
+    fn schedule_suppresses_reroutes_and_digests() {
+        use chrono::NaiveTime;
+
+        use crate::schedule::QuietWindow;
+
+        let all_day = || QuietWindow::new(NaiveTime::MIN, NaiveTime::MIN);
+        let schedule = Schedule::new(chrono_tz::UTC)
+            .window(all_day().up_to(tracing::Level::DEBUG), ScheduleAction::Suppress)
+            .window(all_day().up_to(tracing::Level::INFO), ScheduleAction::Digest)
+            .window(
+                all_day().up_to(tracing::Level::WARN),
+                ScheduleAction::Reroute("http://localhost/pager".into()),
+            );
+        let (layer, worker) = WebhookLayer::<TestConfig, TestFactory>::builder("test".into(), Default::default())
+            .schedule(schedule)
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("suppressed");
+            tracing::info!("first held");
+            tracing::info!("second held");
+            tracing::warn!("rerouted");
+            tracing::error!("sent");
+        });
+
+        let received = received(&worker);
+        let routed: Vec<(&str, &str)> = received
+            .iter()
+            .map(|inputs| (inputs["message"].as_str().unwrap(), inputs["webhook_url"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            routed,
+            vec![
+                ("Digest of 2 events held back during quiet hours", "http://localhost"),
+                ("rerouted", "http://localhost/pager"),
+                ("sent", "http://localhost"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejected_events_are_recorded() {
+        let rejections = FilterRejections::new(10);
+        let (layer, _worker) =
+            WebhookLayer::<TestConfig, TestFactory>::builder("test".into(), Regex::new("^wanted").unwrap().into())
+                .message_filters(EventFilters::new(None, Some(vec![Regex::new("ignore me").unwrap()])))
+                .record_rejections(rejections.clone())
+                .build();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "unwanted", "hello");
+            tracing::info!(target: "wanted", "please ignore me");
+            tracing::info!(target: "wanted", "hello");
+        });
+
+        assert_eq!(rejections.count(FilterKind::Target), 1);
+        assert_eq!(rejections.count(FilterKind::Message), 1);
+        let recent = rejections.recent();
+        assert_eq!(recent[0].target, "unwanted");
+        assert_eq!(recent[1].filter, FilterKind::Message);
     }
 }
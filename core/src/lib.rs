@@ -1,17 +1,40 @@
+use std::any::Any;
+use std::collections::BTreeMap;
 use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
 
+use chrono::{DateTime, Utc};
+
+use serde::Deserialize;
 use serde_json::Value;
 use tracing::{Level};
 
+pub use error_chain::EventError;
 pub use filters::EventFilters;
+pub use occurrences::Occurrences;
 pub use worker::BackgroundWorker;
+pub use worker::ScheduledMessage;
+pub use worker::WebhookClient;
 pub use worker::WorkerMessage;
 
 
+pub mod backtrace;
+pub mod config;
+mod error_chain;
 pub mod filters;
 mod worker;
 pub mod layer;
-mod aws_lambda;
+mod occurrences;
+pub mod otel;
+pub mod panic;
+pub mod rate_limit;
+pub mod reload;
+pub mod schedule;
+pub mod thread;
+#[cfg(feature = "aws-lambda")]
+pub mod aws_lambda;
 
 pub type ChannelSender = tokio::sync::mpsc::UnboundedSender<WorkerMessage>;
 pub type ChannelReceiver = tokio::sync::mpsc::UnboundedReceiver<WorkerMessage>;
@@ -20,14 +43,111 @@ pub type ChannelReceiver = tokio::sync::mpsc::UnboundedReceiver<WorkerMessage>;
 pub trait WebhookMessage: Debug + Send + Sync {
     fn webhook_url(&self) -> &str;
     fn serialize(&self) -> String;
+
+    /// Further bodies posted to the same webhook right after this message, for content that
+    /// does not fit into a single message.
+    fn follow_ups(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Check the body of the response to a delivered request, for endpoints which report errors in
+    /// successful responses. Rejected requests are reported, and not retried.
+    ///
+    /// Returns a reference identifying the delivered message, if the endpoint reports one, e.g.
+    /// the `ts` of a Slack message.
+    fn check_response(&self, _body: &str) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(None)
+    }
+
+    /// The channel this message is posted to, when each message chooses its own. Thread and update
+    /// keys only relate messages posted to the same channel.
+    fn channel(&self) -> Option<&str> {
+        None
+    }
+
+    /// Relates this message to earlier ones with the same key, which the platform may show as a
+    /// thread.
+    fn thread_key(&self) -> Option<&str> {
+        None
+    }
+
+    /// The body to send instead of [`serialize`](Self::serialize) when replying to the first message
+    /// with the same thread key, identified by the reference returned for it by `check_response`.
+    fn serialize_reply(&self, _reference: &str) -> String {
+        self.serialize()
+    }
+
+    /// The bodies to send instead of [`follow_ups`](Self::follow_ups) when this message is sent as
+    /// a reply, so that they are posted into the same thread.
+    fn serialize_follow_up_replies(&self, _reference: &str) -> Vec<String> {
+        self.follow_ups()
+    }
+
+    /// Relates this message to earlier duplicates with the same key. Instead of being sent again,
+    /// a duplicate updates the first message sent with the key, see
+    /// [`serialize_update`](Self::serialize_update).
+    fn update_key(&self) -> Option<&str> {
+        None
+    }
+
+    /// The URL and body of a request updating this message, identified by the reference returned
+    /// for it by `check_response`, to show how often it and its duplicates have occurred. Called on
+    /// the first message with an update key when a duplicate arrives. Returns `None` to send the
+    /// duplicate as usual instead.
+    fn serialize_update(&self, _reference: &str, _occurrences: &Occurrences) -> Option<(String, String)> {
+        None
+    }
+
+    /// Upload content sent separately from this message, such as files too large to include in
+    /// it, once the message has been delivered. `reference` identifies the thread the message
+    /// was posted in, as returned by `check_response` for its first message.
+    ///
+    /// The client sends the headers of the layer's [`Config`] to its API.
+    fn upload_attachments<'a>(&'a self, _client: &'a WebhookClient, _reference: &'a str) -> Option<UploadFuture<'a>> {
+        None
+    }
 }
 
+/// Uploads the attachments of a message, see [`WebhookMessage::upload_attachments`].
+pub type UploadFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>> + Send + 'a>>;
+
 pub trait WebhookMessageFactory {
     fn create(inputs: WebhookMessageInputs) -> impl WebhookMessage;
 }
 
+/// A [`WebhookMessageFactory`] with settings of its own, such as who Slack messages mention,
+/// configured with [`WebhookLayerBuilder::platform_settings`](layer::WebhookLayerBuilder::platform_settings).
+pub trait ConfigurableFactory: WebhookMessageFactory {
+    /// The settings, passed to the factory in [`WebhookMessageInputs::platform`].
+    type Settings: Send + Sync + 'static;
+}
+
+/// The settings of a [`ConfigurableFactory`], as configured on the layer's builder.
+#[derive(Clone, Default)]
+pub struct PlatformSettings(Option<Arc<dyn Any + Send + Sync>>);
+
+impl PlatformSettings {
+    pub fn new<T: Any + Send + Sync>(settings: T) -> Self {
+        Self(Some(Arc::new(settings)))
+    }
+
+    /// The settings, if they were configured and are of type `T`.
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.0.as_deref()?.downcast_ref()
+    }
+}
+
+impl Debug for PlatformSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PlatformSettings").field(&self.0.is_some()).finish()
+    }
+}
 
 /// The data expected to be available for message producers.
+///
+/// Fields are added as the layer grows, so inputs built outside the layer, e.g. in tests, should
+/// fill the fields they do not set with `..Default::default()`.
 pub struct WebhookMessageInputs {
     pub app_name: String,
     pub message: String,
@@ -38,6 +158,93 @@ pub struct WebhookMessageInputs {
     pub source_line: u32,
     pub source_file: String,
     pub event_level: Level,
+    /// The number of similar events suppressed by rate limiting or sampling since the last one sent.
+    pub suppressed_events: u64,
+    /// The errors recorded as fields of the event, with their source chains. A field named `error`
+    /// comes first.
+    pub errors: Vec<EventError>,
+    /// A backtrace of the code which emitted the event, trimmed to the application's crates, if
+    /// backtrace capture is enabled for the event's level.
+    pub backtrace: Option<String>,
+    /// When the event occurred.
+    pub timestamp: DateTime<Utc>,
+    /// Static context about the process which produced the event, such as its host, environment
+    /// and version, as configured on the layer's builder.
+    pub context: BTreeMap<String, String>,
+    /// The OpenTelemetry trace the event was emitted in, with the `opentelemetry` feature.
+    pub trace: Option<otel::TraceContext>,
+    /// The AWS Lambda invocation the event was emitted in, with the `aws-lambda` feature.
+    pub lambda: Option<LambdaInvocation>,
+    /// The layout requested on the layer's builder, if any. Platforms use their default otherwise.
+    pub format: Option<MessageFormat>,
+    /// Whether platforms escape markup in the event's text, so that it is shown as logged.
+    pub escape_markup: bool,
+    /// The channel to post the message to, when sending through the platform's API rather than an
+    /// incoming webhook.
+    pub channel: Option<String>,
+    /// Relates the message to earlier ones with the same key, see [`thread::ThreadBy`].
+    pub thread_key: Option<String>,
+    /// Identifies the event's callsite, see [`filters::callsite_id`]. Empty for digests.
+    pub callsite: String,
+    /// The runbook for the event, from the template configured on the layer's builder.
+    pub runbook_url: Option<String>,
+    /// The settings of the platform's factory, see [`ConfigurableFactory`].
+    pub platform: PlatformSettings,
+}
+
+impl Default for WebhookMessageInputs {
+    /// An `ERROR` event occurring now, without any details, whose markup is escaped.
+    fn default() -> Self {
+        Self {
+            app_name: String::new(),
+            message: String::new(),
+            target: String::new(),
+            span: String::new(),
+            metadata: String::new(),
+            webhook_url: String::new(),
+            source_line: 0,
+            source_file: String::new(),
+            event_level: Level::ERROR,
+            suppressed_events: 0,
+            errors: Vec::new(),
+            backtrace: None,
+            timestamp: Utc::now(),
+            context: BTreeMap::new(),
+            trace: None,
+            lambda: None,
+            format: None,
+            escape_markup: true,
+            channel: None,
+            thread_key: None,
+            callsite: String::new(),
+            runbook_url: None,
+            platform: PlatformSettings::default(),
+        }
+    }
+}
+
+/// How messages are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageFormat {
+    /// A rich layout, such as Slack blocks or Discord embeds.
+    Rich,
+    /// Markdown text, in the flavour of the platform.
+    PlainText,
+    /// Slack's legacy attachments, with a bar colored by level. Platforms without attachments use
+    /// their rich layout.
+    Attachments,
+}
+
+/// The AWS Lambda function and invocation which produced an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LambdaInvocation {
+    /// The id of the current invocation's request, once known.
+    pub request_id: Option<String>,
+    pub function_name: String,
+    pub function_version: String,
+    /// A link to the function's log stream in the CloudWatch console, filtered to the request.
+    pub log_url: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -54,6 +261,31 @@ pub trait Config {
     fn new_from_env() -> Self
     where
         Self: Sized;
+
+    /// Headers sent with requests to [`Config::api_url`], e.g. an `Authorization` header carrying
+    /// an API token.
+    fn headers(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    /// The base URL of the platform's API. The [`Config::headers`] are only sent to this URL and
+    /// the URLs below it, never to other hosts such as rerouted webhooks. Defaults to the webhook
+    /// URL.
+    fn api_url(&self) -> &str {
+        self.webhook_url()
+    }
+
+    /// The channel messages are posted to, when sending through the platform's API rather than an
+    /// incoming webhook, which is tied to a single channel.
+    fn channel(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// A [`Config`] which can be created from a webhook URL alone, as required to build a layer from
+/// a [`config::LayerConfig`].
+pub trait FromWebhookUrl: Config {
+    fn new_from_webhook_url(webhook_url: String) -> Self;
 }
 
 #[cfg(test)]
@@ -0,0 +1,75 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+
+/// How many buckets the recent history is kept in.
+const BUCKETS: usize = 12;
+/// How long each bucket is, so that the history covers the last hour.
+const BUCKET_SECS: i64 = 5 * 60;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// How often duplicates of a message were sent, tracked by the worker for messages which update
+/// the first duplicate in place, see [`WebhookMessage::update_key`](crate::WebhookMessage::update_key).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Occurrences {
+    /// How many times the message was sent, including the first time.
+    pub count: u64,
+    /// When the message was first sent.
+    pub first: DateTime<Utc>,
+    /// When the message was last sent.
+    pub last: DateTime<Utc>,
+    /// Occurrences per five minutes over the last hour, oldest first.
+    recent: VecDeque<u64>,
+}
+
+impl Occurrences {
+    pub(crate) fn new(at: DateTime<Utc>) -> Self {
+        let mut recent = VecDeque::from(vec![0; BUCKETS]);
+        recent[BUCKETS - 1] = 1;
+        Self {
+            count: 1,
+            first: at,
+            last: at,
+            recent,
+        }
+    }
+
+    pub(crate) fn record(&mut self, at: DateTime<Utc>) {
+        let elapsed = at.timestamp().div_euclid(BUCKET_SECS) - self.last.timestamp().div_euclid(BUCKET_SECS);
+        for _ in 0..elapsed.clamp(0, BUCKETS as i64) {
+            self.recent.pop_front();
+            self.recent.push_back(0);
+        }
+        if let Some(current) = self.recent.back_mut() {
+            *current += 1;
+        }
+        self.count += 1;
+        self.last = self.last.max(at);
+    }
+
+    /// A sparkline of the occurrences over the last hour, in five minute steps, e.g. `▁▁▁▂▁▁▃▁▁▅▇█`.
+    pub fn sparkline(&self) -> String {
+        let max = self.recent.iter().copied().max().unwrap_or(0).max(1);
+        self.recent
+            .iter()
+            .map(|&count| SPARKS[((count * (SPARKS.len() as u64 - 1)).div_ceil(max)) as usize])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sparkline_shows_the_last_hour() {
+        let start = DateTime::from_timestamp(1_699_999_800, 0).unwrap();
+        let mut occurrences = Occurrences::new(start);
+        for minutes in [50, 55, 55, 56, 57, 58, 59, 59] {
+            occurrences.record(start + chrono::Duration::minutes(minutes));
+        }
+        assert_eq!(occurrences.count, 9);
+        assert_eq!(occurrences.last, start + chrono::Duration::minutes(59));
+        assert_eq!(occurrences.sparkline(), "▂▁▁▁▁▁▁▁▁▁▂█");
+    }
+}
@@ -0,0 +1,61 @@
+/// The OpenTelemetry trace and span an event was emitted in.
+///
+/// Only available with the `opentelemetry` feature, when the subscriber includes a
+/// `tracing_opentelemetry::OpenTelemetryLayer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    /// The trace id, as 32 lowercase hex digits.
+    pub trace_id: String,
+    /// The span id, as 16 lowercase hex digits.
+    pub span_id: String,
+    /// A link to the trace in a trace viewer, built from the layer's trace URL template.
+    pub url: Option<String>,
+}
+
+impl TraceContext {
+    #[cfg(any(test, feature = "opentelemetry"))]
+    pub(crate) fn new(trace_id: String, span_id: String, url_template: Option<&str>) -> Self {
+        let url = url_template.map(|template| {
+            template
+                .replace("{trace_id}", &trace_id)
+                .replace("{span_id}", &span_id)
+        });
+        Self { trace_id, span_id, url }
+    }
+
+    /// The trace context of the innermost span in scope with a valid OpenTelemetry context.
+    #[cfg(feature = "opentelemetry")]
+    pub(crate) fn from_span<S>(
+        span: &tracing_subscriber::registry::SpanRef<'_, S>,
+        url_template: Option<&str>,
+    ) -> Option<Self>
+    where
+        S: for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        span.scope().find_map(|span| {
+            let extensions = span.extensions();
+            let data = extensions.get::<tracing_opentelemetry::OtelData>()?;
+            let (trace_id, span_id) = (data.trace_id()?, data.span_id()?);
+            (trace_id != opentelemetry::TraceId::INVALID)
+                .then(|| Self::new(trace_id.to_string(), span_id.to_string(), url_template))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_template_is_filled_in() {
+        let trace = TraceContext::new(
+            "4bf92f3577b34da6a3ce929d0e0e4736".to_string(),
+            "00f067aa0ba902b7".to_string(),
+            Some("https://traces.example.com/trace/{trace_id}?span={span_id}"),
+        );
+        assert_eq!(
+            trace.url.as_deref(),
+            Some("https://traces.example.com/trace/4bf92f3577b34da6a3ce929d0e0e4736?span=00f067aa0ba902b7")
+        );
+    }
+}
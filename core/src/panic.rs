@@ -0,0 +1,239 @@
+use std::{
+    any::Any,
+    panic::{Location, PanicHookInfo},
+    sync::mpsc,
+    time::{Duration, SystemTime},
+};
+
+use tracing::Level;
+
+use crate::{
+    backtrace::BacktraceCapture,
+    filters::callsite_id,
+    layer::MessageSettings,
+    reload::SharedLayerFilters,
+    ChannelSender,
+    WebhookMessage,
+    WebhookMessageInputs,
+    WorkerMessage,
+};
+
+/// How long a panicking thread waits for the report to be sent, by default.
+const DEFAULT_FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A panic hook reporting panics through a webhook layer.
+///
+/// Panics bypass tracing entirely, so they are reported by a hook installed with
+/// [`PanicHook::install`]. The hook sends the panic payload, location and thread name (and,
+/// optionally, a backtrace) to the layer's background worker, then blocks the panicking thread
+/// until the worker has sent it or the flush timeout elapses, so that the report is delivered
+/// before the process aborts. The previously installed hook runs afterwards.
+///
+/// The worker must be running for the report to be delivered. When the worker runs on a
+/// single-threaded runtime and the panic happens on that runtime's thread, the report is only sent
+/// once the panic has unwound.
+pub struct PanicHook {
+    settings: MessageSettings,
+    filters: SharedLayerFilters,
+    sender: ChannelSender,
+    create: fn(WebhookMessageInputs) -> Box<dyn WebhookMessage>,
+    backtrace: Option<BacktraceCapture>,
+    flush_timeout: Duration,
+}
+
+impl PanicHook {
+    pub(crate) fn new(
+        settings: MessageSettings,
+        filters: SharedLayerFilters,
+        sender: ChannelSender,
+        create: fn(WebhookMessageInputs) -> Box<dyn WebhookMessage>,
+    ) -> Self {
+        Self {
+            settings,
+            filters,
+            sender,
+            create,
+            backtrace: None,
+            flush_timeout: DEFAULT_FLUSH_TIMEOUT,
+        }
+    }
+
+    pub(crate) fn with_backtrace(mut self, backtrace: Option<BacktraceCapture>) -> Self {
+        self.backtrace = backtrace;
+        self
+    }
+
+    /// Attach a backtrace of the panicking thread, trimmed to the application's crates. Defaults to
+    /// the backtrace capture of the layer, if any; the capture's level is ignored for panics.
+    pub fn capture_backtraces(mut self, capture: BacktraceCapture) -> Self {
+        self.backtrace = Some(capture);
+        self
+    }
+
+    /// How long the panicking thread waits for the report to be sent, 2 seconds by default.
+    pub fn flush_timeout(mut self, timeout: Duration) -> Self {
+        self.flush_timeout = timeout;
+        self
+    }
+
+    /// Install the hook, keeping the previously installed hook to run after reporting the panic.
+    pub fn install(self) {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info: &PanicHookInfo<'_>| {
+            self.report(info.payload(), info.location());
+            previous(info);
+        }));
+    }
+
+    /// Send a report of a panic to the worker, and wait for it to be sent. Panics at a muted
+    /// location are not reported, so that the Mute button of their reports works as for events.
+    fn report(&self, payload: &(dyn Any + Send), location: Option<&Location<'_>>) {
+        let inputs = self.inputs(payload, location);
+        let muted = self
+            .filters
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .mutes
+            .is_muted(&inputs.callsite, SystemTime::now());
+        if muted {
+            return;
+        }
+        let message = (self.create)(inputs);
+        if let Err(e) = self.sender.send(WorkerMessage::Data(message)) {
+            println!("ERROR: failed to send panic report to webhook message worker: {}", e);
+            return;
+        }
+        let (flushed, wait) = mpsc::channel();
+        if self.sender.send(WorkerMessage::Flush(flushed)).is_ok()
+            && wait.recv_timeout(self.flush_timeout).is_err()
+        {
+            println!("ERROR: timed out waiting for the panic report to be sent to the webhook");
+        }
+    }
+
+    fn inputs(&self, payload: &(dyn Any + Send), location: Option<&Location<'_>>) -> WebhookMessageInputs {
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("Box<dyn Any>");
+        let thread = std::thread::current();
+        let thread_name = thread.name().unwrap_or("<unnamed>");
+        let metadata = serde_json::json!({
+            "thread": thread_name,
+            "column": location.map(|location| location.column()),
+        });
+        let webhook_url = self
+            .filters
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .webhook_url
+            .clone();
+        let message = format!("thread '{}' panicked: {}", thread_name, message);
+        let source_file = location.map_or("Unknown", |location| location.file());
+        let source_line = location.map_or(0, |location| location.line());
+        WebhookMessageInputs {
+            metadata: serde_json::to_string_pretty(&metadata).unwrap_or_default(),
+            source_line,
+            source_file: source_file.to_string(),
+            backtrace: self.backtrace.as_ref().and_then(BacktraceCapture::capture_now),
+            callsite: callsite_id("panic", source_file, source_line),
+            ..self.settings.inputs(message, "panic", Level::ERROR, webhook_url)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use super::*;
+    use crate::{
+        filters::{EventFilters, Mutes},
+        reload::LayerFilters,
+    };
+
+    #[derive(Debug)]
+    struct Report(String);
+
+    impl WebhookMessage for Report {
+        fn webhook_url(&self) -> &str {
+            "http://localhost"
+        }
+
+        fn serialize(&self) -> String {
+            self.0.clone()
+        }
+    }
+
+    fn create(inputs: WebhookMessageInputs) -> Box<dyn WebhookMessage> {
+        Box::new(Report(format!(
+            "{} at {}#L{} to {}",
+            inputs.message, inputs.source_file, inputs.source_line, inputs.webhook_url
+        )))
+    }
+
+    /// Report a panic at this location, returning the reports received by the worker.
+    fn report(mutes: Mutes, location: &'static Location<'static>) -> Vec<String> {
+        let filters = LayerFilters {
+            target_filters: EventFilters::default(),
+            message_filters: None,
+            event_by_field_filters: None,
+            field_exclusion_filters: None,
+            field_redaction_filters: None,
+            webhook_url: "http://localhost/panics".to_string(),
+            mutes,
+        };
+        let (sender, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let filters = Arc::new(RwLock::new(Arc::new(filters)));
+        let settings = MessageSettings {
+            app_name: "test".to_string(),
+            ..Default::default()
+        };
+        let hook = PanicHook::new(settings, filters, sender, create);
+        let worker = std::thread::spawn(move || {
+            let mut reports = Vec::new();
+            while let Some(message) = rx.blocking_recv() {
+                match message {
+                    WorkerMessage::Data(payload) => reports.push(payload.serialize()),
+                    WorkerMessage::Flush(flushed) => {
+                        let _ = flushed.send(());
+                        return reports;
+                    }
+                    _ => {}
+                }
+            }
+            reports
+        });
+
+        std::thread::Builder::new()
+            .name("payments".to_string())
+            .spawn(move || hook.report(&"card declined", Some(location)))
+            .unwrap()
+            .join()
+            .unwrap();
+        worker.join().unwrap()
+    }
+
+    #[test]
+    fn reports_are_flushed_by_the_worker() {
+        let location = Location::caller();
+        assert_eq!(
+            report(Mutes::default(), location),
+            vec![format!(
+                "thread 'payments' panicked: card declined at {}#L{} to http://localhost/panics",
+                location.file(),
+                location.line()
+            )]
+        );
+    }
+
+    #[test]
+    fn muted_locations_are_not_reported() {
+        let location = Location::caller();
+        let mut mutes = Mutes::default();
+        let until = SystemTime::now() + Duration::from_secs(3600);
+        mutes.mute(callsite_id("panic", location.file(), location.line()), until);
+        assert!(report(mutes, location).is_empty());
+    }
+}
@@ -0,0 +1,302 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::Value;
+use tracing::{callsite::Identifier, Level, Metadata};
+
+/// The maximum number of keys tracked. Once reached, idle keys are forgotten, then the least
+/// recently used ones down to [`EVICT_TO_KEYS`], so that the next keys are tracked without
+/// evicting again.
+const MAX_TRACKED_KEYS: usize = 4096;
+const EVICT_TO_KEYS: usize = MAX_TRACKED_KEYS / 4 * 3;
+
+/// A token-bucket rate limit applied to the events of each callsite.
+///
+/// Each callsite (and, optionally, each distinct value of a field) may send `burst` events at once,
+/// after which it is refilled at a rate of `burst` events per `period`.
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    burst: u32,
+    period: Duration,
+    by_field: Option<String>,
+}
+
+impl RateLimit {
+    /// Allow up to `burst` events per `period` from each callsite.
+    pub fn new(burst: u32, period: Duration) -> Self {
+        Self {
+            burst,
+            period,
+            by_field: None,
+        }
+    }
+
+    /// Additionally key the limit by the value of the given event field, e.g. `user_id`, so that each
+    /// value gets its own bucket.
+    pub fn by_field(mut self, field: impl Into<String>) -> Self {
+        self.by_field = Some(field.into());
+        self
+    }
+}
+
+/// Probabilistic sampling of events, per level.
+///
+/// Each level has a rate between `0.0` (drop every event) and `1.0` (keep every event, the default).
+#[derive(Debug, Clone)]
+pub struct Sampling {
+    rates: [f64; 5],
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Self { rates: [1.0; 5] }
+    }
+}
+
+impl Sampling {
+    /// Keep roughly `rate` of the events at the given level.
+    pub fn level(mut self, level: Level, rate: f64) -> Self {
+        self.rates[level_index(level)] = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    fn rate(&self, level: Level) -> f64 {
+        self.rates[level_index(level)]
+    }
+}
+
+fn level_index(level: Level) -> usize {
+    match level {
+        Level::TRACE => 0,
+        Level::DEBUG => 1,
+        Level::INFO => 2,
+        Level::WARN => 3,
+        Level::ERROR => 4,
+    }
+}
+
+/// Why an event was suppressed by the [`Limiter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Suppressed {
+    RateLimited,
+    Sampled,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+    /// When the key was last checked, on the limiter's clock of checks.
+    last_seen: u64,
+    suppressed: u64,
+}
+
+/// Applies the sampling and rate limit of a layer, and counts the events it suppressed.
+#[derive(Debug)]
+pub(crate) struct Limiter {
+    rate_limit: Option<RateLimit>,
+    sampling: Option<Sampling>,
+    buckets: Mutex<HashMap<(Identifier, Option<String>), Bucket>>,
+    checks: AtomicU64,
+    rng: AtomicU64,
+}
+
+impl Limiter {
+    pub(crate) fn new(rate_limit: Option<RateLimit>, sampling: Option<Sampling>) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or(0);
+        Self {
+            rate_limit,
+            sampling,
+            buckets: Mutex::new(HashMap::new()),
+            checks: AtomicU64::new(0),
+            rng: AtomicU64::new(seed | 1),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.rate_limit.is_some() || self.sampling.is_some()
+    }
+
+    /// Decide whether an event may be sent.
+    ///
+    /// Returns the number of events from the same callsite (and field value) suppressed since the
+    /// last one sent, or the reason this event is suppressed.
+    pub(crate) fn check(
+        &self,
+        metadata: &'static Metadata<'static>,
+        fields: &HashMap<&str, Value>,
+    ) -> Result<u64, Suppressed> {
+        let field_value = self
+            .rate_limit
+            .as_ref()
+            .and_then(|rate_limit| rate_limit.by_field.as_deref())
+            .and_then(|field| fields.get(field))
+            .map(|value| match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            });
+        let key = (metadata.callsite(), field_value);
+        let now = Instant::now();
+        let check = self.checks.fetch_add(1, Ordering::Relaxed);
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if buckets.len() >= MAX_TRACKED_KEYS && !buckets.contains_key(&key) {
+            self.forget_idle(&mut buckets, now);
+            forget_least_recently_used(&mut buckets, EVICT_TO_KEYS);
+        }
+        let burst = self.rate_limit.as_ref().map_or(0.0, |rate_limit| f64::from(rate_limit.burst));
+        let bucket = buckets.entry(key).or_insert_with(|| Bucket {
+            tokens: burst,
+            last_refill: now,
+            last_seen: check,
+            suppressed: 0,
+        });
+        bucket.last_seen = check;
+
+        if let Some(sampling) = &self.sampling {
+            if self.next_random() >= sampling.rate(*metadata.level()) {
+                bucket.suppressed += 1;
+                return Err(Suppressed::Sampled);
+            }
+        }
+        if let Some(rate_limit) = &self.rate_limit {
+            refill(bucket, rate_limit, now);
+            if bucket.tokens < 1.0 {
+                bucket.suppressed += 1;
+                return Err(Suppressed::RateLimited);
+            }
+            bucket.tokens -= 1.0;
+        }
+        Ok(std::mem::take(&mut bucket.suppressed))
+    }
+
+    /// Forget buckets which are full and have nothing left to report.
+    fn forget_idle(&self, buckets: &mut HashMap<(Identifier, Option<String>), Bucket>, now: Instant) {
+        buckets.retain(|_, bucket| {
+            if let Some(rate_limit) = &self.rate_limit {
+                refill(bucket, rate_limit, now);
+                bucket.suppressed > 0 || bucket.tokens < f64::from(rate_limit.burst)
+            } else {
+                bucket.suppressed > 0
+            }
+        });
+    }
+
+    /// A uniformly distributed number in `[0, 1)`, from a xorshift generator.
+    fn next_random(&self) -> f64 {
+        let mut x = self.rng.load(Ordering::Relaxed);
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng.store(x, Ordering::Relaxed);
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Forget the least recently used buckets, keeping at most `keep`.
+fn forget_least_recently_used(buckets: &mut HashMap<(Identifier, Option<String>), Bucket>, keep: usize) {
+    let excess = buckets.len().saturating_sub(keep);
+    if excess == 0 {
+        return;
+    }
+    // Each check is seen by a single bucket, so the buckets seen at or after the cutoff are kept.
+    let mut last_seen: Vec<u64> = buckets.values().map(|bucket| bucket.last_seen).collect();
+    let (_, cutoff, _) = last_seen.select_nth_unstable(excess);
+    let cutoff = *cutoff;
+    buckets.retain(|_, bucket| bucket.last_seen >= cutoff);
+}
+
+fn refill(bucket: &mut Bucket, rate_limit: &RateLimit, now: Instant) {
+    let burst = f64::from(rate_limit.burst);
+    let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+    let per_second = if rate_limit.period.is_zero() {
+        f64::INFINITY
+    } else {
+        burst / rate_limit.period.as_secs_f64()
+    };
+    bucket.tokens = (bucket.tokens + elapsed * per_second).min(burst);
+    bucket.last_refill = now;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> &'static Metadata<'static> {
+        struct Callsite;
+        static CALLSITE: Callsite = Callsite;
+        static METADATA: Metadata<'static> = tracing::metadata! {
+            name: "test",
+            target: "test",
+            level: Level::WARN,
+            fields: &[],
+            callsite: &CALLSITE,
+            kind: tracing::metadata::Kind::EVENT,
+        };
+        impl tracing::callsite::Callsite for Callsite {
+            fn set_interest(&self, _: tracing::subscriber::Interest) {}
+
+            fn metadata(&self) -> &Metadata<'_> {
+                &METADATA
+            }
+        }
+        &METADATA
+    }
+
+    #[test]
+    fn rate_limit_reports_suppressed_count_on_next_event() {
+        let limiter = Limiter::new(Some(RateLimit::new(2, Duration::from_millis(100))), None);
+        let fields = HashMap::new();
+        assert_eq!(limiter.check(metadata(), &fields), Ok(0));
+        assert_eq!(limiter.check(metadata(), &fields), Ok(0));
+        assert_eq!(limiter.check(metadata(), &fields), Err(Suppressed::RateLimited));
+        assert_eq!(limiter.check(metadata(), &fields), Err(Suppressed::RateLimited));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(limiter.check(metadata(), &fields), Ok(2));
+    }
+
+    #[test]
+    fn rate_limit_by_field_uses_separate_buckets() {
+        let limiter = Limiter::new(Some(RateLimit::new(1, Duration::from_secs(60)).by_field("user")), None);
+        let alice = HashMap::from([("user", Value::from("alice"))]);
+        let bob = HashMap::from([("user", Value::from("bob"))]);
+        assert_eq!(limiter.check(metadata(), &alice), Ok(0));
+        assert_eq!(limiter.check(metadata(), &alice), Err(Suppressed::RateLimited));
+        assert_eq!(limiter.check(metadata(), &bob), Ok(0));
+    }
+
+    #[test]
+    fn least_recently_used_keys_are_forgotten_at_capacity() {
+        let limiter = Limiter::new(Some(RateLimit::new(1, Duration::from_secs(60)).by_field("request")), None);
+        let request = |n: usize| HashMap::from([("request", Value::from(n))]);
+        for n in 0..MAX_TRACKED_KEYS {
+            assert_eq!(limiter.check(metadata(), &request(n)), Ok(0));
+        }
+        assert_eq!(limiter.check(metadata(), &request(0)), Err(Suppressed::RateLimited));
+
+        assert_eq!(limiter.check(metadata(), &request(MAX_TRACKED_KEYS)), Ok(0));
+        assert_eq!(limiter.buckets.lock().unwrap().len(), EVICT_TO_KEYS + 1);
+        assert_eq!(limiter.check(metadata(), &request(0)), Err(Suppressed::RateLimited));
+        assert_eq!(limiter.check(metadata(), &request(1)), Ok(0));
+    }
+
+    #[test]
+    fn sampling_drops_levels_with_zero_rate() {
+        let limiter = Limiter::new(None, Some(Sampling::default().level(Level::WARN, 0.0)));
+        let fields = HashMap::new();
+        assert_eq!(limiter.check(metadata(), &fields), Err(Suppressed::Sampled));
+
+        let limiter = Limiter::new(None, Some(Sampling::default().level(Level::ERROR, 0.0)));
+        assert_eq!(limiter.check(metadata(), &fields), Ok(0));
+    }
+}
@@ -0,0 +1,246 @@
+use std::{
+    path::PathBuf,
+    sync::{mpsc, Arc, RwLock},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use regex::Regex;
+use tracing::metadata::LevelFilter;
+use tracing_subscriber::EnvFilter;
+
+use crate::{
+    config::{ConfigError, LayerConfig},
+    filters::{CallsiteFilter, EventFilters, Mutes},
+};
+
+/// The filters and destination of a webhook layer, which may be swapped at runtime through a
+/// [`ReloadHandle`].
+#[derive(Debug, Clone)]
+pub struct LayerFilters {
+    /// Filter events by their target.
+    pub target_filters: EventFilters,
+    /// Filter events by their message.
+    pub message_filters: Option<EventFilters>,
+    /// Filter events by the keys of their fields.
+    pub event_by_field_filters: Option<EventFilters>,
+    /// Exclude event fields whose key matches any of these expressions.
+    pub field_exclusion_filters: Option<Vec<Regex>>,
+    /// Redact the values of fields whose key matches any of these expressions.
+    pub field_redaction_filters: Option<Vec<Regex>>,
+    /// The webhook URL messages are sent to.
+    pub webhook_url: String,
+    /// The callsites muted at runtime.
+    pub mutes: Mutes,
+}
+
+pub(crate) type SharedLayerFilters = Arc<RwLock<Arc<LayerFilters>>>;
+
+/// A handle for reloading the filters and destination of a running webhook layer, similar to
+/// `tracing_subscriber::reload::Handle`.
+///
+/// Obtain a handle from `WebhookLayer::reload_handle` before installing the layer. The background
+/// worker keeps running while the layer is reloaded, and each event is processed with either the
+/// old or the new filters, never a mixture of both.
+#[derive(Debug, Clone)]
+pub struct ReloadHandle {
+    filters: SharedLayerFilters,
+    callsite_filter: CallsiteFilter,
+}
+
+impl ReloadHandle {
+    pub(crate) fn new(filters: SharedLayerFilters, callsite_filter: CallsiteFilter) -> Self {
+        Self {
+            filters,
+            callsite_filter,
+        }
+    }
+
+    /// A snapshot of the current filters and destination.
+    pub fn current(&self) -> Arc<LayerFilters> {
+        self.filters.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    /// Modify the filters and destination in place, then swap them into the layer at once.
+    pub fn modify(&self, f: impl FnOnce(&mut LayerFilters)) {
+        let mut filters = self.filters.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut modified = LayerFilters::clone(&filters);
+        f(&mut modified);
+        *filters = Arc::new(modified);
+    }
+
+    /// Replace the filters and destination.
+    pub fn reload(&self, filters: LayerFilters) {
+        *self.filters.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(filters);
+    }
+
+    /// Replace the level threshold.
+    pub fn reload_level(&self, level: Option<LevelFilter>) {
+        self.callsite_filter.set_level(level);
+        tracing::callsite::rebuild_interest_cache();
+    }
+
+    /// Replace the level threshold and the `EnvFilter` directives.
+    pub fn reload_callsite_filter(&self, level: Option<LevelFilter>, directives: Option<EnvFilter>) {
+        self.callsite_filter.set(level, directives);
+        tracing::callsite::rebuild_interest_cache();
+    }
+
+    /// Replace the webhook URL messages are sent to.
+    pub fn reload_webhook_url(&self, webhook_url: String) {
+        self.modify(|filters| filters.webhook_url = webhook_url);
+    }
+
+    /// Mute a callsite, identified by [`callsite_id`](crate::filters::callsite_id), for the given
+    /// duration.
+    pub fn mute_callsite(&self, callsite: &str, duration: Duration) {
+        let until = SystemTime::now() + duration;
+        self.modify(|filters| filters.mutes.mute(callsite, until));
+    }
+
+    pub fn unmute_callsite(&self, callsite: &str) {
+        self.modify(|filters| filters.mutes.unmute(callsite));
+    }
+
+    /// Replace every filter, the level threshold and the destination from a declarative
+    /// configuration.
+    ///
+    /// The configuration is validated first; if it is invalid, the layer is left untouched. The
+    /// application name, rate limit, sampling and schedule cannot be changed at runtime, the
+    /// current webhook URL is kept if the configuration has no destination, and muted callsites
+    /// stay muted. The filters and the level threshold are swapped under the same lock, so that
+    /// no concurrent mute or reload is lost.
+    pub fn reload_config(&self, config: &LayerConfig) -> Result<(), ConfigError> {
+        let validated = config.validate()?;
+        self.modify(|filters| {
+            *filters = LayerFilters {
+                target_filters: validated.target_filters,
+                message_filters: validated.message_filters,
+                event_by_field_filters: validated.event_by_field_filters,
+                field_exclusion_filters: validated.field_exclusion_filters,
+                field_redaction_filters: validated.field_redaction_filters,
+                webhook_url: validated.webhook_url.unwrap_or_else(|| filters.webhook_url.clone()),
+                mutes: std::mem::take(&mut filters.mutes),
+            };
+            self.callsite_filter.set(validated.level, validated.directives);
+        });
+        tracing::callsite::rebuild_interest_cache();
+        Ok(())
+    }
+
+    /// Watch a configuration file, reloading the layer whenever the file is modified.
+    ///
+    /// The file's modification time is polled every `interval` on a background thread. Invalid
+    /// configurations are reported and otherwise ignored. Watching stops when the returned watcher
+    /// is dropped.
+    pub fn watch_config_file(&self, path: impl Into<PathBuf>, interval: Duration) -> ConfigWatcher {
+        let path = path.into();
+        let handle = self.clone();
+        let (stop, stopped) = mpsc::channel::<()>();
+        let modified = |path: &PathBuf| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        thread::spawn(move || {
+            let mut last_modified: Option<SystemTime> = modified(&path);
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let current = modified(&path);
+                if current.is_none() || current == last_modified {
+                    continue;
+                }
+                last_modified = current;
+                match LayerConfig::from_file(&path).and_then(|config| handle.reload_config(&config)) {
+                    Ok(()) => println!("reloaded webhook layer configuration from {}", path.display()),
+                    Err(e) => println!("ERROR: failed to reload webhook layer configuration: {}", e),
+                }
+            }
+        });
+        ConfigWatcher { _stop: stop }
+    }
+}
+
+/// Watches a configuration file on behalf of a [`ReloadHandle`], until dropped.
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    _stop: mpsc::Sender<()>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handle() -> ReloadHandle {
+        let filters = LayerFilters {
+            target_filters: EventFilters::default(),
+            message_filters: None,
+            event_by_field_filters: None,
+            field_exclusion_filters: None,
+            field_redaction_filters: None,
+            webhook_url: "http://localhost/initial".to_string(),
+            mutes: Mutes::default(),
+        };
+        ReloadHandle::new(Arc::new(RwLock::new(Arc::new(filters))), CallsiteFilter::new(None, None))
+    }
+
+    #[test]
+    fn mutes_are_kept_across_concurrent_reloads() {
+        let handle = handle();
+        let config = LayerConfig::from_json_str(r#"{ "app_name": "app", "level": "warn" }"#).unwrap();
+        let reloader = {
+            let handle = handle.clone();
+            thread::spawn(move || {
+                for _ in 0..200 {
+                    handle.reload_config(&config).unwrap();
+                }
+            })
+        };
+        for n in 0..200 {
+            handle.mute_callsite(&n.to_string(), Duration::from_secs(60));
+        }
+        reloader.join().unwrap();
+
+        let current = handle.current();
+        let now = SystemTime::now();
+        assert!((0..200).all(|n| current.mutes.is_muted(&n.to_string(), now)));
+        assert_eq!(current.webhook_url, "http://localhost/initial");
+    }
+
+    #[test]
+    fn watched_configuration_files_are_reloaded() {
+        let path = std::env::temp_dir().join(format!("tracing-layer-reload-{}.json", std::process::id()));
+        let write = |contents: &str, modified_secs: u64| {
+            std::fs::write(&path, contents).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(modified_secs)))
+                .unwrap();
+        };
+        let config = |webhook_url: &str| {
+            format!(r#"{{ "app_name": "app", "destination": {{ "webhook_url": "{}" }} }}"#, webhook_url)
+        };
+        let handle = handle();
+        let reloaded_to = |webhook_url: &str, attempts: usize| {
+            (0..attempts).any(|_| {
+                thread::sleep(Duration::from_millis(5));
+                handle.current().webhook_url == webhook_url
+            })
+        };
+
+        write(&config("http://localhost/unchanged"), 1);
+        let watcher = handle.watch_config_file(&path, Duration::from_millis(1));
+        assert!(!reloaded_to("http://localhost/unchanged", 20));
+
+        write(&config("http://localhost/modified"), 2);
+        assert!(reloaded_to("http://localhost/modified", 1000));
+
+        write("{ invalid", 3);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(handle.current().webhook_url, "http://localhost/modified");
+        write(&config("http://localhost/fixed"), 4);
+        assert!(reloaded_to("http://localhost/fixed", 1000));
+
+        drop(watcher);
+        thread::sleep(Duration::from_millis(20));
+        write(&config("http://localhost/unwatched"), 5);
+        assert!(!reloaded_to("http://localhost/unwatched", 20));
+        let _ = std::fs::remove_file(&path);
+    }
+}
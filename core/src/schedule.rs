@@ -0,0 +1,297 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use serde_json::Value;
+use tracing::Level;
+
+use crate::{layer::MessageSettings, WebhookMessage, WebhookMessageInputs};
+
+/// What happens to events which fall into a [`QuietWindow`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleAction {
+    /// Drop the events.
+    Suppress,
+    /// Hold the events back, and send a single digest of them when the window ends.
+    Digest,
+    /// Send the events to another webhook URL instead.
+    Reroute(String),
+}
+
+/// A recurring period of time, such as weeknights from 22:00 to 07:00.
+///
+/// A window whose end is before its start spans midnight, and belongs to the day it starts on. A
+/// window whose start and end are equal lasts the whole day.
+#[derive(Debug, Clone)]
+pub struct QuietWindow {
+    days: Vec<Weekday>,
+    start: NaiveTime,
+    end: NaiveTime,
+    up_to: Level,
+}
+
+impl QuietWindow {
+    /// Create a window applying every day, to events of every level.
+    pub fn new(start: NaiveTime, end: NaiveTime) -> Self {
+        Self {
+            days: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+                Weekday::Sat,
+                Weekday::Sun,
+            ],
+            start,
+            end,
+            up_to: Level::ERROR,
+        }
+    }
+
+    /// Only apply the window on the given days.
+    pub fn days(mut self, days: impl IntoIterator<Item = Weekday>) -> Self {
+        self.days = days.into_iter().collect();
+        self
+    }
+
+    /// Only apply the window to events no more severe than the given level, e.g. `Level::WARN`
+    /// applies it to WARN events and anything more verbose, but not to ERROR events.
+    pub fn up_to(mut self, level: Level) -> Self {
+        self.up_to = level;
+        self
+    }
+
+    /// If the given time falls into this window, the time at which the window ends.
+    fn active_until<Z: TimeZone>(&self, now: &DateTime<Z>) -> Option<DateTime<Z>> {
+        let date = now.date_naive();
+        let time = now.time();
+        let today = self.days.contains(&now.weekday());
+        let yesterday = self.days.contains(&now.weekday().pred());
+        let end_date = if self.start < self.end {
+            (today && self.start <= time && time < self.end).then_some(date)
+        } else if today && time >= self.start {
+            date.succ_opt()
+        } else if yesterday && time < self.end {
+            Some(date)
+        } else {
+            None
+        }?;
+        Some(local_time(&now.timezone(), end_date, self.end).unwrap_or_else(|| now.clone() + Duration::hours(1)))
+    }
+}
+
+fn local_time<Z: TimeZone>(timezone: &Z, date: NaiveDate, time: NaiveTime) -> Option<DateTime<Z>> {
+    timezone.from_local_datetime(&date.and_time(time)).earliest()
+}
+
+/// Quiet hours and schedule-based routing for a webhook layer.
+///
+/// Each window is checked in order, in the schedule's time zone, and the action of the first
+/// window applying to an event is taken. Events outside of every window are sent as usual.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    timezone: Tz,
+    windows: Vec<(QuietWindow, ScheduleAction)>,
+}
+
+impl Schedule {
+    /// Create an empty schedule in the given time zone.
+    pub fn new(timezone: Tz) -> Self {
+        Self {
+            timezone,
+            windows: Vec::new(),
+        }
+    }
+
+    /// Take the given action for events falling into the window.
+    pub fn window(mut self, window: QuietWindow, action: ScheduleAction) -> Self {
+        self.windows.push((window, action));
+        self
+    }
+
+    /// The index and action of the first window applying to an event, and the time it ends.
+    pub(crate) fn action_for(&self, level: Level, now: DateTime<Utc>) -> Option<(usize, &ScheduleAction, SystemTime)> {
+        let now = now.with_timezone(&self.timezone);
+        self.windows
+            .iter()
+            .enumerate()
+            .filter(|(_, (window, _))| level >= window.up_to)
+            .find_map(|(index, (window, action))| {
+                window
+                    .active_until(&now)
+                    .map(|end| (index, action, SystemTime::from(end.with_timezone(&Utc))))
+            })
+    }
+}
+
+/// A single event held back for a digest.
+#[derive(Debug, Clone)]
+pub(crate) struct DigestEntry {
+    pub(crate) time: DateTime<Utc>,
+    pub(crate) level: Level,
+    pub(crate) target: String,
+    pub(crate) message: String,
+}
+
+/// The most events listed in a digest. Later events in the same window are only counted, so that a
+/// hot loop during quiet hours neither grows memory without bound nor produces a huge digest.
+const MAX_DIGEST_ENTRIES: usize = 100;
+
+#[derive(Debug, Default)]
+struct PendingDigest {
+    webhook_url: String,
+    entries: Vec<DigestEntry>,
+    /// The events held back after the first [`MAX_DIGEST_ENTRIES`], which are not listed.
+    unlisted: usize,
+    /// The most severe level of every event held back, listed or not.
+    level: Option<Level>,
+}
+
+/// The events held back for digests, per window.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Digests {
+    pending: Arc<Mutex<HashMap<usize, PendingDigest>>>,
+}
+
+impl Digests {
+    /// Hold back an event. Returns `true` if this is the first event of a new digest, in which case
+    /// the caller must schedule the digest with [`Digests::take`].
+    pub(crate) fn hold(&self, window: usize, webhook_url: &str, entry: DigestEntry) -> bool {
+        let mut pending = self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let digest = pending.entry(window).or_default();
+        let first = digest.entries.is_empty();
+        digest.webhook_url = webhook_url.to_string();
+        // More severe levels compare as smaller.
+        digest.level = Some(digest.level.map_or(entry.level, |level| level.min(entry.level)));
+        if digest.entries.len() < MAX_DIGEST_ENTRIES {
+            digest.entries.push(entry);
+        } else {
+            digest.unlisted += 1;
+        }
+        first
+    }
+
+    /// Build the message for a window's digest, if any events were held back.
+    pub(crate) fn take(
+        &self,
+        window: usize,
+        settings: &MessageSettings,
+        create: fn(WebhookMessageInputs) -> Box<dyn WebhookMessage>,
+    ) -> Option<Box<dyn WebhookMessage>> {
+        let digest = {
+            let mut pending = self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            pending.remove(&window)?
+        };
+        let level = digest.level?;
+        let entries: Vec<Value> = digest
+            .entries
+            .iter()
+            .map(|entry| {
+                serde_json::json!({
+                    "time": entry.time.to_rfc3339(),
+                    "level": entry.level.as_str(),
+                    "target": entry.target,
+                    "message": entry.message,
+                })
+            })
+            .collect();
+        let message = match digest.unlisted {
+            0 => format!("Digest of {} events held back during quiet hours", entries.len()),
+            unlisted => format!(
+                "Digest of {} events held back during quiet hours, {} more were not listed",
+                entries.len() + unlisted,
+                unlisted
+            ),
+        };
+        Some(create(WebhookMessageInputs {
+            metadata: serde_json::to_string_pretty(&entries).unwrap_or_default(),
+            runbook_url: None,
+            ..settings.inputs(message, "", level, digest.webhook_url)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        // 2024-01-01 was a Monday.
+        chrono_tz::Europe::Berlin
+            .with_ymd_and_hms(2024, 1, day, hour, minute, 0)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn weeknights() -> Schedule {
+        let (start, end) = (NaiveTime::from_hms_opt(22, 0, 0).unwrap(), NaiveTime::from_hms_opt(7, 0, 0).unwrap());
+        let window = QuietWindow::new(start, end)
+            .days([Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri])
+            .up_to(Level::WARN);
+        Schedule::new(chrono_tz::Europe::Berlin).window(window, ScheduleAction::Digest)
+    }
+
+    #[test]
+    fn windows_span_midnight_in_the_schedule_timezone() {
+        let schedule = weeknights();
+        let (_, action, end) = schedule.action_for(Level::WARN, at(1, 23, 30)).unwrap();
+        assert_eq!(action, &ScheduleAction::Digest);
+        assert_eq!(SystemTime::from(at(2, 7, 0)), end);
+        assert!(schedule.action_for(Level::WARN, at(2, 6, 59)).is_some());
+        assert!(schedule.action_for(Level::WARN, at(2, 7, 0)).is_none());
+        assert!(schedule.action_for(Level::WARN, at(1, 12, 0)).is_none());
+    }
+
+    #[test]
+    fn windows_respect_days_and_levels() {
+        let schedule = weeknights();
+        // Saturday night is not quiet, but Friday night continues into Saturday morning.
+        assert!(schedule.action_for(Level::INFO, at(6, 23, 0)).is_none());
+        assert!(schedule.action_for(Level::INFO, at(6, 6, 0)).is_some());
+        assert!(schedule.action_for(Level::ERROR, at(1, 23, 30)).is_none());
+    }
+
+    #[derive(Debug)]
+    struct Digest(String);
+
+    impl WebhookMessage for Digest {
+        fn webhook_url(&self) -> &str {
+            "http://localhost"
+        }
+
+        fn serialize(&self) -> String {
+            self.0.clone()
+        }
+    }
+
+    fn create(inputs: WebhookMessageInputs) -> Box<dyn WebhookMessage> {
+        let listed = serde_json::from_str::<Vec<Value>>(&inputs.metadata).unwrap().len();
+        Box::new(Digest(format!("{} ({}, {} listed)", inputs.message, inputs.event_level, listed)))
+    }
+
+    #[test]
+    fn digests_list_a_bounded_number_of_events() {
+        let digests = Digests::default();
+        for n in 0..MAX_DIGEST_ENTRIES + 5 {
+            let entry = DigestEntry {
+                time: Utc::now(),
+                level: if n == MAX_DIGEST_ENTRIES + 2 { Level::WARN } else { Level::INFO },
+                target: "app".to_string(),
+                message: format!("event {}", n),
+            };
+            assert_eq!(digests.hold(0, "http://localhost", entry), n == 0);
+        }
+
+        let digest = digests.take(0, &MessageSettings::default(), create).unwrap();
+        assert_eq!(
+            digest.serialize(),
+            "Digest of 105 events held back during quiet hours, 5 more were not listed (WARN, 100 listed)"
+        );
+        assert!(digests.take(0, &MessageSettings::default(), create).is_none());
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    hash::{Hash, Hasher},
+};
+
+/// How many threads (or updated messages) the worker remembers. The oldest are forgotten first,
+/// and their next event starts afresh.
+const MAX_REMEMBERED: usize = 1000;
+
+/// What relates the events posted into one thread, on platforms which support threads.
+///
+/// The first event with a key is posted as usual, and later events with the same key as replies
+/// to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThreadBy {
+    /// Events with the same value of this field, e.g. `request_id`. The field is looked up on the
+    /// event first, then on its spans from the innermost outwards.
+    Field(String),
+    /// Events with the same fingerprint: the same callsite and message.
+    Fingerprint,
+}
+
+/// Identify events from the same callsite with the same message, within a single process.
+pub fn fingerprint(target: &str, file: &str, line: u32, message: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    (target, file, line, message).hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Something remembered by the worker per key, such as the first message posted for each thread
+/// key. Only the most recent keys are remembered.
+#[derive(Debug)]
+pub(crate) struct Remembered<V> {
+    values: HashMap<String, V>,
+    order: VecDeque<String>,
+}
+
+impl<V> Default for Remembered<V> {
+    fn default() -> Self {
+        Self {
+            values: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+}
+
+impl<V> Remembered<V> {
+    pub(crate) fn get(&self, key: &str) -> Option<&V> {
+        self.values.get(key)
+    }
+
+    pub(crate) fn get_mut(&mut self, key: &str) -> Option<&mut V> {
+        self.values.get_mut(key)
+    }
+
+    /// Remember a value for a new key. Keys already remembered keep their value.
+    pub(crate) fn insert(&mut self, key: &str, value: V) {
+        if self.values.contains_key(key) {
+            return;
+        }
+        if self.order.len() >= MAX_REMEMBERED {
+            if let Some(oldest) = self.order.pop_front() {
+                self.values.remove(&oldest);
+            }
+        }
+        self.order.push_back(key.to_string());
+        self.values.insert(key.to_string(), value);
+    }
+
+    pub(crate) fn remove(&mut self, key: &str) {
+        if self.values.remove(key).is_some() {
+            self.order.retain(|remembered| remembered != key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oldest_keys_are_forgotten() {
+        let mut threads = Remembered::default();
+        for n in 0..=MAX_REMEMBERED {
+            threads.insert(&n.to_string(), format!("ts-{}", n));
+        }
+        threads.insert("1", "replaced".to_string());
+        assert_eq!(threads.get("0"), None);
+        assert_eq!(threads.get("1").map(String::as_str), Some("ts-1"));
+        assert_eq!(threads.get(&MAX_REMEMBERED.to_string()).map(String::as_str), Some("ts-1000"));
+    }
+}
@@ -1,11 +1,14 @@
 use std::fmt::Debug;
 use std::sync::Arc;
+use std::time::SystemTime;
 
+use chrono::Utc;
 use tokio::task::JoinHandle;
 use debug_print::debug_println;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use tokio::sync::Mutex;
 
-use crate::{ChannelReceiver, ChannelSender, WebhookMessage};
+use crate::{occurrences::Occurrences, thread::Remembered, ChannelReceiver, ChannelSender, WebhookMessage};
 
 /// Maximum number of retries for failed requests
 const MAX_RETRIES: usize = 10;
@@ -37,6 +40,9 @@ pub struct BackgroundWorker {
     /// This receiver is wrapped in an `Arc<Mutex<>>` to allow shared mutable access
     /// between the `start` function and the worker task.
     pub(crate) rx: Arc<Mutex<ChannelReceiver>>,
+
+    /// Sends the worker's requests, with the headers of the layer's `Config`.
+    pub(crate) client: WebhookClient,
 }
 
 impl BackgroundWorker {
@@ -47,9 +53,10 @@ impl BackgroundWorker {
     /// spawns a task to process messages.
     pub async fn start(&self) {
         let rx = self.rx.clone();
+        let client = self.client.clone();
         let future = async move {
             let mut rx = rx.lock().await;
-            worker(&mut *rx).await;
+            worker(&mut rx, &client).await;
         };
         let handle = tokio::spawn(future);
         let mut guard = self.handle.lock().await;
@@ -77,54 +84,256 @@ impl BackgroundWorker {
         }
     }
 
+    /// Waits until every message sent to the worker so far has been sent to its webhook.
+    ///
+    /// Scheduled messages, such as digests, are not sent early. The worker must have been started,
+    /// otherwise this waits until it is.
+    pub async fn flush(&self) {
+        let (flushed, wait) = std::sync::mpsc::channel();
+        if let Err(e) = self.sender.send(WorkerMessage::Flush(flushed)) {
+            println!("ERROR: failed to send flush message to webhook message worker: {}", e);
+            return;
+        }
+        let _ = tokio::task::spawn_blocking(move || wait.recv()).await;
+    }
+
 }
 
 /// A command sent to a worker containing a new message that should be sent to a webhook endpoint.
 #[derive(Debug)]
 pub enum WorkerMessage {
     Data(Box<dyn WebhookMessage>),
+    /// A message to build and send later, such as a digest of events held back during quiet hours.
+    Scheduled(ScheduledMessage),
+    /// Signal the sender once every message received before this one has been sent. Scheduled
+    /// messages are not sent early.
+    Flush(std::sync::mpsc::Sender<()>),
     Shutdown,
 }
 
+/// A message built and sent by the worker at a later time.
+///
+/// If the worker is shut down first, the message is built and sent immediately instead.
+pub struct ScheduledMessage {
+    pub(crate) at: SystemTime,
+    pub(crate) build: Box<dyn FnOnce() -> Option<Box<dyn WebhookMessage>> + Send>,
+}
+
+impl ScheduledMessage {
+    /// Schedule a message to be built at the given time. The message is skipped if `build` returns
+    /// `None`.
+    pub fn new(at: SystemTime, build: impl FnOnce() -> Option<Box<dyn WebhookMessage>> + Send + 'static) -> Self {
+        Self {
+            at,
+            build: Box::new(build),
+        }
+    }
+}
+
+impl Debug for ScheduledMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScheduledMessage").field("at", &self.at).finish_non_exhaustive()
+    }
+}
+
 /// Provides a background worker task that sends the messages generated by the layer.
-pub(crate) async fn worker(rx: &mut ChannelReceiver) {
-    let client = reqwest::Client::new();
-    while let Some(message) = rx.recv().await {
+pub(crate) async fn worker(rx: &mut ChannelReceiver, client: &WebhookClient) {
+    let mut sent = Sent::default();
+    let mut scheduled: Vec<ScheduledMessage> = Vec::new();
+    loop {
+        let next_due = scheduled.iter().map(|message| message.at).min();
+        let message = match next_due {
+            Some(at) => {
+                let delay = at.duration_since(SystemTime::now()).unwrap_or_default();
+                tokio::select! {
+                    message = rx.recv() => message,
+                    _ = tokio::time::sleep(delay) => {
+                        let now = SystemTime::now();
+                        let (due, pending) = scheduled.into_iter().partition(|message| message.at <= now);
+                        scheduled = pending;
+                        send_scheduled(client, &mut sent, due).await;
+                        continue;
+                    }
+                }
+            }
+            None => rx.recv().await,
+        };
         match message {
-            WorkerMessage::Data(payload) => {
-                let webhook_url = payload.webhook_url();
-                let payload_json = payload.serialize();
-                println!("sending webhook message: {}", &payload_json);
-
-                let mut retries = 0;
-                while retries < MAX_RETRIES {
-                    match client
-                        .post(webhook_url)
-                        .header("Content-Type", "application/json")
-                        .body(payload_json.clone())
-                        .send()
-                        .await
-                    {
-                        Ok(res) => {
-                            debug_println!("webhook message sent: {:?}", &res);
-                            let res_text = res.text().await.unwrap();
-                            debug_println!("webhook message response: {}", res_text);
-                            break; // Success, break out of the retry loop
-                        }
-                        Err(e) => {
-                            println!("ERROR: failed to send webhook message: {}", e);
-                        }
-                    };
+            Some(WorkerMessage::Data(payload)) => send(client, &mut sent, payload).await,
+            Some(WorkerMessage::Scheduled(message)) => scheduled.push(message),
+            Some(WorkerMessage::Flush(flushed)) => {
+                let _ = flushed.send(());
+            }
+            Some(WorkerMessage::Shutdown) | None => {
+                send_scheduled(client, &mut sent, scheduled).await;
+                break;
+            }
+        }
+    }
+}
+
+/// What the worker remembers about the messages it has sent.
+#[derive(Default)]
+struct Sent {
+    /// The first message sent for each thread key.
+    threads: Remembered<String>,
+    /// The message sent for each update key, its reference, and how often it has occurred since.
+    updates: Remembered<(String, Occurrences, Box<dyn WebhookMessage>)>,
+}
+
+async fn send_scheduled(client: &WebhookClient, sent: &mut Sent, messages: Vec<ScheduledMessage>) {
+    for message in messages {
+        if let Some(payload) = (message.build)() {
+            send(client, sent, payload).await;
+        }
+    }
+}
+
+/// Sends the requests of a worker. The headers of the layer's [`Config`](crate::Config) are only
+/// attached to requests aimed at its API, so that credentials never reach other hosts, such as
+/// rerouted webhooks or the upload URLs handed out by the API.
+#[derive(Debug, Clone, Default)]
+pub struct WebhookClient {
+    client: reqwest::Client,
+    api_url: String,
+    headers: HeaderMap,
+}
 
-                    // Exponential backoff - increase the delay between retries
-                    let delay_ms = 2u64.pow(retries as u32) * 100;
-                    tokio::time::sleep(std::time::Duration::from_millis(delay_ms)).await;
-                    retries += 1;
+impl WebhookClient {
+    /// A client sending the given headers to `api_url` and every URL below it. Invalid headers are
+    /// reported and skipped.
+    pub(crate) fn new(api_url: &str, headers: &[(String, String)]) -> Self {
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            match (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+                (Ok(name), Ok(mut value)) => {
+                    value.set_sensitive(true);
+                    header_map.insert(name, value);
                 }
+                _ => println!("ERROR: invalid webhook request header: {}", name),
             }
-            WorkerMessage::Shutdown => {
-                break;
+        }
+        Self {
+            client: reqwest::Client::new(),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            headers: header_map,
+        }
+    }
+
+    /// Start a `POST` request to the given URL, with the configured headers if it is aimed at the API.
+    pub fn post(&self, url: &str) -> reqwest::RequestBuilder {
+        let request = self.client.post(url);
+        if self.is_api_url(url) {
+            request.headers(self.headers.clone())
+        } else {
+            request
+        }
+    }
+
+    fn is_api_url(&self, url: &str) -> bool {
+        match url.strip_prefix(self.api_url.as_str()) {
+            Some(rest) if !self.api_url.is_empty() => {
+                rest.is_empty() || rest.starts_with('/') || rest.starts_with('?')
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Send a message and its follow-ups to their webhook, retrying failed requests.
+///
+/// A message with a thread key is sent as a reply to the first message sent with that key. A
+/// message with an update key updates the first message sent with that key instead, if any, and
+/// is sent as usual if the update fails. Its attachments are uploaded into the thread once the
+/// message is delivered.
+async fn send(client: &WebhookClient, sent: &mut Sent, payload: Box<dyn WebhookMessage>) {
+    let update_key = payload.update_key().map(|key| channel_key(payload.as_ref(), key));
+    if let Some(key) = &update_key {
+        if let Some((reference, occurrences, first)) = sent.updates.get_mut(key) {
+            occurrences.record(Utc::now());
+            if let Some((url, payload_json)) = first.serialize_update(reference, occurrences) {
+                let updated = match post(client, &url, payload_json).await {
+                    Some(response) => first.check_response(&response).map_err(|e| e.to_string()),
+                    None => Err("request failed".to_string()),
+                };
+                match updated {
+                    Ok(_) => return,
+                    Err(e) => {
+                        println!("ERROR: webhook message update rejected, sending it as a new message: {}", e);
+                        sent.updates.remove(key);
+                    }
+                }
+            }
+        }
+    }
+
+    let webhook_url = payload.webhook_url();
+    let thread_key = payload.thread_key().map(|key| channel_key(payload.as_ref(), key));
+    let root = thread_key.as_deref().and_then(|key| sent.threads.get(key)).cloned();
+    let (first, follow_ups) = match &root {
+        Some(reference) => (payload.serialize_reply(reference), payload.serialize_follow_up_replies(reference)),
+        None => (payload.serialize(), payload.follow_ups()),
+    };
+    let mut updated_by = None;
+    for (index, payload_json) in std::iter::once(first).chain(follow_ups).enumerate() {
+        if let Some(response) = post(client, webhook_url, payload_json).await {
+            match payload.check_response(&response) {
+                Ok(Some(reference)) if index == 0 => {
+                    let thread = root.as_deref().unwrap_or(&reference);
+                    if let Some(upload) = payload.upload_attachments(client, thread) {
+                        if let Err(e) = upload.await {
+                            println!("ERROR: failed to upload webhook message attachments: {}", e);
+                        }
+                    }
+                    if let Some(key) = thread_key.as_deref().filter(|_| root.is_none()) {
+                        sent.threads.insert(key, reference.clone());
+                    }
+                    updated_by = Some(reference);
+                }
+                Ok(_) => {}
+                Err(e) => println!("ERROR: webhook message rejected: {}", e),
             }
         }
     }
+    // Later duplicates update this message, so it is kept to build the updates from.
+    if let (Some(key), Some(reference)) = (&update_key, updated_by) {
+        sent.updates.insert(key, (reference, Occurrences::new(Utc::now()), payload));
+    }
+}
+
+/// The key a message is remembered by: its thread or update key, within the channel it is posted to.
+fn channel_key(payload: &dyn WebhookMessage, key: &str) -> String {
+    format!("{} {}", payload.channel().unwrap_or_default(), key)
+}
+
+/// Post a body to a webhook, returning the body of the response once delivered.
+async fn post(client: &WebhookClient, webhook_url: &str, payload_json: String) -> Option<String> {
+    println!("sending webhook message: {}", &payload_json);
+
+    let mut retries = 0;
+    while retries < MAX_RETRIES {
+        match client
+            .post(webhook_url)
+            .header("Content-Type", "application/json")
+            .body(payload_json.clone())
+            .send()
+            .await
+        {
+            Ok(res) => {
+                debug_println!("webhook message sent: {:?}", &res);
+                let res_text = res.text().await.unwrap_or_default();
+                debug_println!("webhook message response: {}", res_text);
+                return Some(res_text);
+            }
+            Err(e) => {
+                println!("ERROR: failed to send webhook message: {}", e);
+            }
+        };
+
+        // Exponential backoff - increase the delay between retries
+        let delay_ms = 2u64.pow(retries as u32) * 100;
+        tokio::time::sleep(std::time::Duration::from_millis(delay_ms)).await;
+        retries += 1;
+    }
+    None
 }
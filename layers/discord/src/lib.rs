@@ -3,10 +3,21 @@
 pub use tracing_layer_core::BackgroundWorker;
 pub use tracing_layer_core::layer::WebhookLayer;
 pub use tracing_layer_core::filters::EventFilters;
+pub use tracing_layer_core::config::{ConfigError, LayerConfig};
+pub use tracing_layer_core::MessageFormat;
 use serde::Serialize;
 use serde_json::Value;
 use tracing_layer_core::layer::WebhookLayerBuilder;
-use tracing_layer_core::{Config, WebhookMessage, WebhookMessageFactory, WebhookMessageInputs};
+use tracing_layer_core::otel::TraceContext;
+use tracing_layer_core::{
+    Config,
+    EventError,
+    FromWebhookUrl,
+    LambdaInvocation,
+    WebhookMessage,
+    WebhookMessageFactory,
+    WebhookMessageInputs,
+};
 
 pub struct DiscordLayer;
 
@@ -14,147 +25,317 @@ impl DiscordLayer {
     pub fn builder(app_name: String, target_filters: EventFilters) -> WebhookLayerBuilder<DiscordConfig, Self> {
         WebhookLayer::builder(app_name, target_filters)
     }
+
+    /// Create a builder from a declarative configuration, reporting every invalid setting at once.
+    pub fn builder_from_config(config: &LayerConfig) -> Result<WebhookLayerBuilder<DiscordConfig, Self>, ConfigError> {
+        WebhookLayerBuilder::from_config(config)
+    }
+}
+
+/// The layout used when the layer's builder does not request one.
+#[cfg(feature = "embed")]
+const DEFAULT_FORMAT: MessageFormat = MessageFormat::Rich;
+#[cfg(not(feature = "embed"))]
+const DEFAULT_FORMAT: MessageFormat = MessageFormat::PlainText;
+
+// Maximum characters allowed for a Discord field value
+const MAX_FIELD_VALUE_CHARS: usize = 1024 - 15;
+const MAX_ERROR_MESSAGE_CHARS: usize = 2048 - 15;
+const MAX_BACKTRACE_CHARS: usize = 4096 - 15;
+// Maximum characters allowed for the content of a plain message
+const MAX_CONTENT_CHARS: usize = 2000;
+
+/// Escape user-controlled text shown as markdown, so that it is shown as logged. Quotes, headings
+/// and lists are only escaped at the start of a line, where they take effect.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut line_start = true;
+    for c in text.chars() {
+        let block = matches!(c, '>' | '#' | '-');
+        if matches!(c, '\\' | '*' | '_' | '~' | '`' | '|' | '<' | '[' | ']') || (block && line_start) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        line_start = c == '\n';
+    }
+    escaped
+}
+
+/// Escape user-controlled text shown in a code block, which only ends at three backticks.
+fn escape_code(text: &str) -> String {
+    text.replace("```", "`\u{200B}``")
+}
+
+/// Disable every mention, so that logged text cannot ping `@everyone`.
+fn no_mentions() -> Value {
+    serde_json::json!({ "parse": [] })
 }
 
 impl WebhookMessageFactory for DiscordLayer {
     fn create(inputs: WebhookMessageInputs) -> impl WebhookMessage {
-        let target = inputs.target;
-        let span = inputs.span;
-        let metadata = inputs.metadata;
-        let message = inputs.message;
-        let app_name = inputs.app_name;
-        let source_file = inputs.source_file;
-        let source_line = inputs.source_line;
-        let event_level = inputs.event_level;
-
-        #[cfg(feature = "embed")]
-        {
-            let event_level_emoji = match event_level {
-                tracing::Level::TRACE => ":mag:",
-                tracing::Level::DEBUG => ":bug:",
-                tracing::Level::INFO => ":information_source:",
-                tracing::Level::WARN => ":warning:",
-                tracing::Level::ERROR => ":x:",
-            };
-            let event_level_color = match event_level {
-                tracing::Level::TRACE => 1752220,
-                tracing::Level::DEBUG => 1752220,
-                tracing::Level::INFO => 5763719,
-                tracing::Level::WARN => 15105570,
-                tracing::Level::ERROR => 15548997,
-            };
-
-            // Maximum characters allowed for a Discord field value
-            const MAX_FIELD_VALUE_CHARS: usize = 1024 - 15;
-            const MAX_ERROR_MESSAGE_CHARS: usize = 2048 - 15;
-
-            // Truncate error_message if it exceeds the limit
-            let mut truncated_message = String::new();
-            if message.chars().count() > MAX_ERROR_MESSAGE_CHARS {
-                println!(
-                    "Truncating message to {} characters, original: {}",
-                    MAX_ERROR_MESSAGE_CHARS, message
-                );
-                let mut char_count = 0;
-                for c in message.chars() {
-                    char_count += 1;
-                    if char_count > MAX_ERROR_MESSAGE_CHARS {
-                        break;
-                    }
-                    truncated_message.push(c);
-                }
-            }
-            let message = if truncated_message.is_empty() {
-                message
-            } else {
-                truncated_message
-            };
-
-            let mut discord_embed = serde_json::json!({
-                "title": format!("{} - {} {}", app_name, event_level_emoji, event_level),
-                "description": format!("```rust\n{}\n```", message),
-                "fields": [
-                    {
-                        "name": "Target Span",
-                        "value": format!("`{}::{}`", target, span),
-                        "inline": true
-                    },
-                    {
-                        "name": "Source",
-                        "value": format!("`{}#L{}`", source_file, source_line),
-                        "inline": true
-                    },
-                ],
-                "footer": {
-                    "text": app_name
-                },
-                "color": event_level_color, // Hex value for "red"
-                "thumbnail": {
-                    "url": "https://example.com/error-thumbnail.png"
-                }
-            });
-
-            // Check if metadata exceeds the limit
-            if metadata.len() <= MAX_FIELD_VALUE_CHARS {
-                // Metadata fits within a single field
-                discord_embed["fields"].as_array_mut().unwrap().push(serde_json::json!({
-                    "name": "Metadata",
-                    "value": format!("```json\n{}\n```", metadata),
-                    "inline": false
-                }));
-            } else {
-                // Metadata exceeds the limit, split into multiple fields
-                let mut remaining_metadata = metadata;
-                let mut chunk_number = 1;
-                while !remaining_metadata.is_empty() {
-                    let chunk = remaining_metadata
-                        .chars()
-                        .take(MAX_FIELD_VALUE_CHARS)
-                        .collect::<String>();
-
-                    remaining_metadata = remaining_metadata.chars().skip(MAX_FIELD_VALUE_CHARS).collect();
-
-                    discord_embed["fields"].as_array_mut().unwrap().push(serde_json::json!({
-                        "name": format!("Metadata ({})", chunk_number),
-                        "value": format!("```json\n{}\n```", chunk),
-                        "inline": false
-                    }));
-
-                    chunk_number += 1;
-                }
-            }
-
-            DiscordMessagePayload {
-                content: None,
-                embeds: Some(vec![discord_embed]),
-                webhook_url: inputs.webhook_url,
-            }
+        match inputs.format.unwrap_or(DEFAULT_FORMAT) {
+            // Discord has no attachments, and embeds are colored by level too.
+            MessageFormat::Rich | MessageFormat::Attachments => embed_message(inputs),
+            MessageFormat::PlainText => text_message(inputs),
+        }
+    }
+}
+
+fn level_emoji(level: tracing::Level) -> &'static str {
+    match level {
+        tracing::Level::TRACE => ":mag:",
+        tracing::Level::DEBUG => ":bug:",
+        tracing::Level::INFO => ":information_source:",
+        tracing::Level::WARN => ":warning:",
+        tracing::Level::ERROR => ":x:",
+    }
+}
+
+fn trace_text(trace: &TraceContext) -> String {
+    match &trace.url {
+        Some(url) => format!("[`{}`]({})", trace.trace_id, url),
+        None => format!("`{}` / `{}`", trace.trace_id, trace.span_id),
+    }
+}
+
+fn lambda_text(lambda: &LambdaInvocation) -> String {
+    let request = match (&lambda.request_id, &lambda.log_url) {
+        (Some(request_id), Some(url)) => format!("\n[{}]({})", request_id, url),
+        (Some(request_id), None) => format!("\n`{}`", request_id),
+        (None, _) => String::new(),
+    };
+    format!("`{}:{}`{}", lambda.function_name, lambda.function_version, request)
+}
+
+fn causes_heading(error: &EventError) -> String {
+    if error.field == "error" {
+        "Caused by".to_string()
+    } else {
+        format!("{} caused by", error.field)
+    }
+}
+
+fn causes_text(error: &EventError) -> String {
+    let causes: Vec<String> = error
+        .sources
+        .iter()
+        .enumerate()
+        .map(|(n, source)| format!("{}. {}", n + 1, source))
+        .collect();
+    causes.join("\n")
+}
+
+fn suppressed_text(suppressed_events: u64) -> String {
+    format!("{} similar events since the last message", suppressed_events)
+}
+
+fn embed_message(mut inputs: WebhookMessageInputs) -> DiscordMessagePayload {
+    if inputs.escape_markup {
+        inputs.message = escape_code(&inputs.message);
+        inputs.metadata = escape_code(&inputs.metadata);
+        inputs.backtrace = inputs.backtrace.as_deref().map(escape_code);
+        for error in &mut inputs.errors {
+            error.field = escape_markdown(&error.field);
+            error.sources = error.sources.iter().map(|source| escape_markdown(source)).collect();
         }
-        #[cfg(not(feature = "embed"))]
-        {
-            let event_level = event.metadata().level().as_str();
-            let source_file = event.metadata().file().unwrap_or("Unknown");
-            let source_line = event.metadata().line().unwrap_or(0);
-            let payload = format!(
-                concat!(
-                "*Trace from {}*\n",
-                "*Event [{}]*: \"{}\"\n",
-                "*Target*: _{}_\n",
-                "*Span*: _{}_\n",
-                "*Metadata*:\n",
-                "```",
-                "{}",
-                "```\n",
-                "*Source*: _{}#L{}_",
-                ),
-                app_name, event_level, message, span, target, metadata, source_file, source_line,
-            );
-            DiscordMessagePayload {
-                content: Some(payload),
-                embeds: None,
-                webhook_url: inputs.webhook_url,
-            }
+    }
+    let event_level = inputs.event_level;
+    let event_level_color = match event_level {
+        tracing::Level::TRACE => 1752220,
+        tracing::Level::DEBUG => 1752220,
+        tracing::Level::INFO => 5763719,
+        tracing::Level::WARN => 15105570,
+        tracing::Level::ERROR => 15548997,
+    };
+
+    // Truncate the message if it exceeds the limit
+    let message: String = inputs.message.chars().take(MAX_ERROR_MESSAGE_CHARS).collect();
+
+    // The footer names the application and the context it runs in, e.g. its host.
+    let footer = std::iter::once(inputs.app_name.clone())
+        .chain(inputs.context.iter().map(|(key, value)| format!("{}: {}", key, value)))
+        .collect::<Vec<_>>()
+        .join(" • ");
+
+    let mut discord_embed = serde_json::json!({
+        "title": format!("{} - {} {}", inputs.app_name, level_emoji(event_level), event_level),
+        "description": format!("```rust\n{}\n```", message),
+        "fields": [
+            {
+                "name": "Target Span",
+                "value": format!("`{}::{}`", inputs.target, inputs.span),
+                "inline": true
+            },
+            {
+                "name": "Source",
+                "value": format!("`{}#L{}`", inputs.source_file, inputs.source_line),
+                "inline": true
+            },
+        ],
+        "footer": {
+            "text": footer
+        },
+        "timestamp": inputs.timestamp.to_rfc3339(),
+        "color": event_level_color,
+        "thumbnail": {
+            "url": "https://example.com/error-thumbnail.png"
         }
+    });
+    if let Some(url) = &inputs.runbook_url {
+        discord_embed["url"] = url.as_str().into();
+    }
+    let fields = discord_embed["fields"].as_array_mut().unwrap();
+
+    if let Some(trace) = &inputs.trace {
+        fields.push(serde_json::json!({
+            "name": "Trace",
+            "value": trace_text(trace),
+            "inline": true
+        }));
+    }
+
+    if let Some(lambda) = &inputs.lambda {
+        fields.push(serde_json::json!({
+            "name": "Lambda",
+            "value": lambda_text(lambda),
+            "inline": true
+        }));
+    }
+
+    // List the source chain of each error, truncated to fit a field.
+    for error in inputs.errors.iter().filter(|error| !error.sources.is_empty()) {
+        fields.push(serde_json::json!({
+            "name": causes_heading(error),
+            "value": causes_text(error).chars().take(MAX_FIELD_VALUE_CHARS).collect::<String>(),
+            "inline": false
+        }));
+    }
+
+    if inputs.suppressed_events > 0 {
+        fields.push(serde_json::json!({
+            "name": "Suppressed",
+            "value": suppressed_text(inputs.suppressed_events),
+            "inline": false
+        }));
+    }
+
+    // Check if metadata exceeds the limit
+    let metadata = inputs.metadata;
+    if metadata.len() <= MAX_FIELD_VALUE_CHARS {
+        // Metadata fits within a single field
+        fields.push(serde_json::json!({
+            "name": "Metadata",
+            "value": format!("```json\n{}\n```", metadata),
+            "inline": false
+        }));
+    } else {
+        // Metadata exceeds the limit, split into multiple fields
+        let mut remaining_metadata = metadata;
+        let mut chunk_number = 1;
+        while !remaining_metadata.is_empty() {
+            let chunk = remaining_metadata
+                .chars()
+                .take(MAX_FIELD_VALUE_CHARS)
+                .collect::<String>();
+
+            remaining_metadata = remaining_metadata.chars().skip(MAX_FIELD_VALUE_CHARS).collect();
+
+            fields.push(serde_json::json!({
+                "name": format!("Metadata ({})", chunk_number),
+                "value": format!("```json\n{}\n```", chunk),
+                "inline": false
+            }));
+
+            chunk_number += 1;
+        }
+    }
+
+    let mut embeds = vec![discord_embed];
+    // The backtrace gets an embed of its own, since it rarely fits into a field.
+    if let Some(backtrace) = inputs.backtrace {
+        embeds.push(serde_json::json!({
+            "title": "Backtrace",
+            "description": format!(
+                "```\n{}\n```",
+                backtrace.chars().take(MAX_BACKTRACE_CHARS).collect::<String>()
+            ),
+            "color": event_level_color,
+        }));
+    }
+
+    DiscordMessagePayload {
+        content: None,
+        embeds: Some(embeds),
+        allowed_mentions: inputs.escape_markup.then(no_mentions),
+        webhook_url: inputs.webhook_url,
+    }
+}
+
+fn text_message(mut inputs: WebhookMessageInputs) -> DiscordMessagePayload {
+    if inputs.escape_markup {
+        inputs.app_name = escape_markdown(&inputs.app_name);
+        inputs.message = escape_markdown(&inputs.message);
+        inputs.metadata = escape_code(&inputs.metadata);
+        inputs.backtrace = inputs.backtrace.as_deref().map(escape_code);
+        for error in &mut inputs.errors {
+            error.field = escape_markdown(&error.field);
+            error.sources = error.sources.iter().map(|source| escape_markdown(source)).collect();
+        }
+        inputs.context = inputs
+            .context
+            .iter()
+            .map(|(key, value)| (escape_markdown(key), escape_markdown(value)))
+            .collect();
+    }
+    let mut lines = vec![
+        format!(
+            "{} **{}** from **{}** <t:{}:f>",
+            level_emoji(inputs.event_level),
+            inputs.event_level,
+            inputs.app_name,
+            inputs.timestamp.timestamp()
+        ),
+        format!("> *{}*", inputs.message),
+        format!("**Target Span**: `{}::{}`", inputs.target, inputs.span),
+        format!("**Source**: `{}#L{}`", inputs.source_file, inputs.source_line),
+    ];
+    if let Some(trace) = &inputs.trace {
+        lines.push(format!("**Trace**: {}", trace_text(trace)));
+    }
+    if let Some(lambda) = &inputs.lambda {
+        lines.push(format!("**Lambda**: {}", lambda_text(lambda).replace('\n', " ")));
+    }
+    for error in inputs.errors.iter().filter(|error| !error.sources.is_empty()) {
+        lines.push(format!("**{}**\n{}", causes_heading(error), causes_text(error)));
+    }
+    if inputs.suppressed_events > 0 {
+        lines.push(format!("*{}*", suppressed_text(inputs.suppressed_events)));
+    }
+    if !inputs.context.is_empty() {
+        let context: Vec<String> = inputs
+            .context
+            .iter()
+            .map(|(key, value)| format!("{}: **{}**", key, value))
+            .collect();
+        lines.push(context.join(" • "));
+    }
+    lines.push(format!("**Metadata**:\n```json\n{}\n```", inputs.metadata));
+    if let Some(backtrace) = &inputs.backtrace {
+        lines.push(format!("**Backtrace**:\n```\n{}\n```", backtrace));
+    }
+
+    // The metadata and backtrace come last, so a cut only loses their tail.
+    let mut content = lines.join("\n");
+    if content.chars().count() > MAX_CONTENT_CHARS {
+        const MARKER: &str = "\n```\n…";
+        content = content.chars().take(MAX_CONTENT_CHARS - MARKER.chars().count()).collect();
+        content.push_str(MARKER);
+    }
+    DiscordMessagePayload {
+        content: Some(content),
+        embeds: None,
+        allowed_mentions: inputs.escape_markup.then(no_mentions),
+        webhook_url: inputs.webhook_url,
     }
 }
 
@@ -184,6 +365,12 @@ impl Default for DiscordConfig {
     }
 }
 
+impl FromWebhookUrl for DiscordConfig {
+    fn new_from_webhook_url(webhook_url: String) -> Self {
+        Self::new(webhook_url)
+    }
+}
+
 impl Config for DiscordConfig {
     fn webhook_url(&self) -> &str {
         &self.webhook_url
@@ -202,6 +389,8 @@ pub(crate) struct DiscordMessagePayload {
     content: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     embeds: Option<Vec<Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allowed_mentions: Option<Value>,
     #[serde(skip_serializing)]
     webhook_url: String,
 }
@@ -218,5 +407,145 @@ impl WebhookMessage for DiscordMessagePayload {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    fn inputs() -> WebhookMessageInputs {
+        WebhookMessageInputs {
+            app_name: "test-app".into(),
+            message: "failed to load user".into(),
+            target: "users".into(),
+            span: "load".into(),
+            metadata: "{}".into(),
+            webhook_url: "http://localhost".into(),
+            source_line: 1,
+            source_file: "src/main.rs".into(),
+            event_level: tracing::Level::ERROR,
+            errors: vec![EventError {
+                field: "error".into(),
+                message: "failed to load user".into(),
+                sources: vec!["query failed".into(), "connection reset".into()],
+            }],
+            timestamp: chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            context: std::collections::BTreeMap::from([
+                ("env".to_string(), "prod".to_string()),
+                ("host".to_string(), "web-1".to_string()),
+            ]),
+            callsite: "0123456789abcdef".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn plain_text_is_selectable_at_runtime() {
+        let mut inputs = inputs();
+        inputs.format = Some(MessageFormat::PlainText);
+        let payload: Value = serde_json::from_str(&DiscordLayer::create(inputs).serialize()).unwrap();
+        assert!(payload.get("embeds").is_none());
+        assert_eq!(
+            payload["content"],
+            ":x: **ERROR** from **test-app** <t:1700000000:f>\n\
+             > *failed to load user*\n\
+             **Target Span**: `users::load`\n\
+             **Source**: `src/main.rs#L1`\n\
+             **Caused by**\n1. query failed\n2. connection reset\n\
+             env: **prod** • host: **web-1**\n\
+             **Metadata**:\n```json\n{}\n```"
+        );
+    }
+
+    #[test]
+    fn markup_is_escaped() {
+        assert_eq!(escape_markdown("@everyone *now* `x`"), "@everyone \\*now\\* \\`x\\`");
+        assert_eq!(escape_markdown("> quote\n- item - x"), "\\> quote\n\\- item - x");
+        assert_eq!(escape_code("a```b"), "a`\u{200B}``b");
+
+        let mut inputs = inputs();
+        inputs.format = Some(MessageFormat::PlainText);
+        inputs.message = "@everyone deploy_failed".into();
+        let payload: Value = serde_json::from_str(&DiscordLayer::create(inputs).serialize()).unwrap();
+        assert!(payload["content"].as_str().unwrap().contains("> *@everyone deploy\\_failed*"));
+        assert_eq!(payload["allowed_mentions"], serde_json::json!({ "parse": [] }));
+    }
+
+    #[test]
+    fn escaping_can_be_disabled() {
+        let mut inputs = inputs();
+        inputs.format = Some(MessageFormat::PlainText);
+        inputs.message = "@everyone deploy_failed".into();
+        inputs.escape_markup = false;
+        let payload: Value = serde_json::from_str(&DiscordLayer::create(inputs).serialize()).unwrap();
+        assert!(payload["content"].as_str().unwrap().contains("> *@everyone deploy_failed*"));
+        assert!(payload.get("allowed_mentions").is_none());
+    }
 
+    #[test]
+    fn plain_text_is_truncated_to_the_content_limit() {
+        let mut inputs = inputs();
+        inputs.format = Some(MessageFormat::PlainText);
+        inputs.metadata = "x".repeat(3000);
+        let payload: Value = serde_json::from_str(&DiscordLayer::create(inputs).serialize()).unwrap();
+        let content = payload["content"].as_str().unwrap();
+        assert_eq!(content.chars().count(), MAX_CONTENT_CHARS);
+        assert!(content.ends_with("\n```\n…"));
+    }
+
+    #[cfg(feature = "embed")]
+    #[test]
+    fn error_sources_are_listed() {
+        let payload: Value = serde_json::from_str(&DiscordLayer::create(inputs()).serialize()).unwrap();
+        let field = &payload["embeds"][0]["fields"][2];
+        assert_eq!(field["name"], "Caused by");
+        assert_eq!(field["value"], "1. query failed\n2. connection reset");
+    }
+
+    #[cfg(feature = "embed")]
+    #[test]
+    fn trace_ids_are_shown_without_a_link() {
+        let mut inputs = inputs();
+        inputs.errors.clear();
+        inputs.trace = Some(TraceContext {
+            trace_id: "4bf92f3577b34da6a3ce929d0e0e4736".into(),
+            span_id: "00f067aa0ba902b7".into(),
+            url: None,
+        });
+        let payload: Value = serde_json::from_str(&DiscordLayer::create(inputs).serialize()).unwrap();
+        let field = &payload["embeds"][0]["fields"][2];
+        assert_eq!(field["name"], "Trace");
+        assert_eq!(field["value"], "`4bf92f3577b34da6a3ce929d0e0e4736` / `00f067aa0ba902b7`");
+    }
+
+    #[cfg(feature = "embed")]
+    #[test]
+    fn lambda_function_is_shown_before_the_request_is_known() {
+        let mut inputs = inputs();
+        inputs.errors.clear();
+        inputs.lambda = Some(LambdaInvocation {
+            request_id: None,
+            function_name: "billing".into(),
+            function_version: "$LATEST".into(),
+            log_url: None,
+        });
+        let payload: Value = serde_json::from_str(&DiscordLayer::create(inputs).serialize()).unwrap();
+        let field = &payload["embeds"][0]["fields"][2];
+        assert_eq!(field["name"], "Lambda");
+        assert_eq!(field["value"], "`billing:$LATEST`");
+    }
+
+    #[cfg(feature = "embed")]
+    #[test]
+    fn timestamp_and_context_are_in_the_footer() {
+        let payload: Value = serde_json::from_str(&DiscordLayer::create(inputs()).serialize()).unwrap();
+        assert_eq!(payload["embeds"][0]["timestamp"], "2023-11-14T22:13:20+00:00");
+        assert_eq!(payload["embeds"][0]["footer"]["text"], "test-app • env: prod • host: web-1");
+    }
+
+    #[cfg(feature = "embed")]
+    #[test]
+    fn backtrace_gets_its_own_embed() {
+        let mut inputs = inputs();
+        inputs.backtrace = Some("   0: my_app::main".into());
+        let payload: Value = serde_json::from_str(&DiscordLayer::create(inputs).serialize()).unwrap();
+        assert_eq!(payload["embeds"][1]["title"], "Backtrace");
+        assert_eq!(payload["embeds"][1]["description"], "```\n   0: my_app::main\n```");
+    }
 }
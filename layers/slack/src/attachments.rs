@@ -0,0 +1,55 @@
+//! Typed [legacy attachments](https://api.slack.com/reference/messaging/attachments), which show a
+//! bar colored by level next to the event's details.
+use serde::Serialize;
+
+/// A secondary block of content shown below the message's text.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Attachment {
+    /// The plain text shown in notifications and clients which cannot show attachments.
+    pub fallback: String,
+    /// The color of the bar, as a hex code such as `#ed4245`.
+    pub color: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub text: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<Field>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub footer: Option<String>,
+    /// The time shown next to the footer, in seconds since the Unix epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ts: Option<i64>,
+    /// The parts rendered as `mrkdwn`.
+    pub mrkdwn_in: Vec<&'static str>,
+}
+
+/// A titled value in an attachment's table of fields.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Field {
+    pub title: String,
+    pub value: String,
+    /// Whether the field is narrow enough to be shown next to another one.
+    pub short: bool,
+}
+
+impl Field {
+    pub fn short(title: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            value: value.into(),
+            short: true,
+        }
+    }
+
+    pub fn long(title: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            value: value.into(),
+            short: false,
+        }
+    }
+}
+
+/// The color of the bar, as a hex code.
+pub fn color(rgb: u32) -> String {
+    format!("#{:06x}", rgb & 0xff_ff_ff)
+}
@@ -0,0 +1,272 @@
+//! Typed [Block Kit](https://api.slack.com/reference/block-kit/blocks) blocks, covering the
+//! subset used to lay out tracing events.
+use serde::Serialize;
+
+/// A layout block in a Slack message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    /// Small, muted text such as the application name or a timestamp.
+    Context { elements: Vec<Text> },
+    /// A block of text, optionally followed by a two-column grid of fields.
+    Section {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        text: Option<Text>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        fields: Vec<Text>,
+    },
+    /// Large, bold plain text.
+    Header { text: Text },
+    /// A thin horizontal rule.
+    Divider,
+    /// Formatted text built from lists, quotes and preformatted elements.
+    RichText { elements: Vec<RichTextElement> },
+    /// A row of interactive elements such as buttons.
+    Actions { elements: Vec<Element> },
+}
+
+impl Block {
+    pub fn context(elements: Vec<Text>) -> Self {
+        Block::Context { elements }
+    }
+
+    pub fn section(text: Text) -> Self {
+        Block::Section {
+            text: Some(text),
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn fields(fields: Vec<Text>) -> Self {
+        Block::Section { text: None, fields }
+    }
+
+    pub fn header(text: impl Into<String>) -> Self {
+        Block::Header {
+            text: Text::plain(text),
+        }
+    }
+
+    pub fn rich_text(elements: Vec<RichTextElement>) -> Self {
+        Block::RichText { elements }
+    }
+
+    pub fn actions(elements: Vec<Element>) -> Self {
+        Block::Actions { elements }
+    }
+}
+
+/// An interactive element. Slack sends an interaction request to the app for each use.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Element {
+    Button {
+        text: Text,
+        action_id: String,
+        /// Sent with the interaction request.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        value: Option<String>,
+        /// Opened in the browser when the button is clicked.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        style: Option<ButtonStyle>,
+    },
+}
+
+impl Element {
+    /// A button sending `value` with its interaction request.
+    pub fn button(text: impl Into<String>, action_id: impl Into<String>, value: impl Into<String>) -> Self {
+        Element::Button {
+            text: Text::plain(text),
+            action_id: action_id.into(),
+            value: Some(value.into()),
+            url: None,
+            style: None,
+        }
+    }
+
+    /// A button opening a link.
+    pub fn link_button(text: impl Into<String>, action_id: impl Into<String>, url: impl Into<String>) -> Self {
+        Element::Button {
+            text: Text::plain(text),
+            action_id: action_id.into(),
+            value: None,
+            url: Some(url.into()),
+            style: None,
+        }
+    }
+
+    pub fn styled(self, style: ButtonStyle) -> Self {
+        match self {
+            Element::Button {
+                text,
+                action_id,
+                value,
+                url,
+                ..
+            } => Element::Button {
+                text,
+                action_id,
+                value,
+                url,
+                style: Some(style),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ButtonStyle {
+    Primary,
+    Danger,
+}
+
+/// A text object, either rendered as `mrkdwn` or shown verbatim.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Text {
+    Mrkdwn { text: String },
+    PlainText { text: String, emoji: bool },
+}
+
+impl Text {
+    pub fn mrkdwn(text: impl Into<String>) -> Self {
+        Text::Mrkdwn { text: text.into() }
+    }
+
+    /// Plain text, with emoji shortcodes such as `:x:` rendered.
+    pub fn plain(text: impl Into<String>) -> Self {
+        Text::PlainText {
+            text: text.into(),
+            emoji: true,
+        }
+    }
+}
+
+/// A top-level element of a `rich_text` block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RichTextElement {
+    RichTextSection { elements: Vec<RichText> },
+    RichTextList {
+        style: ListStyle,
+        elements: Vec<RichTextElement>,
+    },
+    RichTextPreformatted { elements: Vec<RichText> },
+    RichTextQuote { elements: Vec<RichText> },
+}
+
+impl RichTextElement {
+    /// An ordered list with one item per line of text.
+    pub fn ordered_list<I, T>(items: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        RichTextElement::RichTextList {
+            style: ListStyle::Ordered,
+            elements: items
+                .into_iter()
+                .map(|item| RichTextElement::RichTextSection {
+                    elements: vec![RichText::text(item)],
+                })
+                .collect(),
+        }
+    }
+
+    pub fn preformatted(text: impl Into<String>) -> Self {
+        RichTextElement::RichTextPreformatted {
+            elements: vec![RichText::text(text)],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListStyle {
+    Bullet,
+    Ordered,
+}
+
+/// An inline run of text within a rich text element.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RichText {
+    Text {
+        text: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        style: Option<TextStyle>,
+    },
+    Link {
+        url: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+    },
+}
+
+impl RichText {
+    pub fn text(text: impl Into<String>) -> Self {
+        RichText::Text {
+            text: text.into(),
+            style: None,
+        }
+    }
+
+    pub fn bold(text: impl Into<String>) -> Self {
+        RichText::Text {
+            text: text.into(),
+            style: Some(TextStyle {
+                bold: true,
+                ..TextStyle::default()
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TextStyle {
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub bold: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub italic: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub code: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn blocks_serialize_with_their_type() {
+        let blocks = vec![
+            Block::header(":x: ERROR"),
+            Block::fields(vec![Text::mrkdwn("*Source*\nsrc/main.rs#L1")]),
+            Block::Divider,
+            Block::rich_text(vec![RichTextElement::ordered_list(vec!["query failed"])]),
+        ];
+        assert_eq!(
+            serde_json::to_value(&blocks).unwrap(),
+            json!([
+                { "type": "header", "text": { "type": "plain_text", "text": ":x: ERROR", "emoji": true } },
+                { "type": "section", "fields": [{ "type": "mrkdwn", "text": "*Source*\nsrc/main.rs#L1" }] },
+                { "type": "divider" },
+                {
+                    "type": "rich_text",
+                    "elements": [{
+                        "type": "rich_text_list",
+                        "style": "ordered",
+                        "elements": [{
+                            "type": "rich_text_section",
+                            "elements": [{ "type": "text", "text": "query failed" }]
+                        }]
+                    }]
+                }
+            ])
+        );
+    }
+}
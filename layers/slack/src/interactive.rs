@@ -0,0 +1,283 @@
+//! Handling the [interaction requests](https://api.slack.com/interactivity/handling) sent when the
+//! buttons added by [`SlackSettings::action_buttons`](crate::SlackSettings::action_buttons)
+//! are clicked, with the `interactive` feature.
+//!
+//! Slack posts a request to the app's interactivity URL for every click. Pass its
+//! `X-Slack-Request-Timestamp` and `X-Slack-Signature` headers and its raw body to
+//! [`InteractionHandler::handle`] from the application's HTTP server, then answer `200 OK`.
+use std::{
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use ring::hmac;
+use serde::Deserialize;
+use tracing_layer_core::reload::ReloadHandle;
+
+use crate::{ACK_ACTION, MUTE_ACTION, MUTE_DURATION};
+
+/// Requests signed longer ago than this are rejected, so that captured requests cannot be replayed.
+const MAX_REQUEST_AGE: Duration = Duration::from_secs(5 * 60);
+
+/// Verifies interaction requests with the app's signing secret, and mutes callsites in the layer
+/// through its [`ReloadHandle`].
+pub struct InteractionHandler {
+    key: hmac::Key,
+    reload_handle: ReloadHandle,
+    client: reqwest::Client,
+}
+
+/// What a verified interaction request did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Interaction {
+    /// A user acknowledged an event.
+    Acknowledged { user: String, callsite: String },
+    /// A user muted an event's callsite for [`MUTE_DURATION`].
+    Muted { user: String, callsite: String },
+    /// Any other interaction, such as opening a runbook.
+    Ignored,
+}
+
+/// Why an interaction request was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InteractionError {
+    /// The request was not signed with the app's signing secret.
+    InvalidSignature,
+    /// The request's timestamp is missing or too old.
+    Expired,
+    /// The request is not an interaction request.
+    InvalidPayload(String),
+}
+
+impl fmt::Display for InteractionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InteractionError::InvalidSignature => write!(f, "the request signature is invalid"),
+            InteractionError::Expired => write!(f, "the request timestamp is missing or too old"),
+            InteractionError::InvalidPayload(e) => write!(f, "invalid interaction payload: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for InteractionError {}
+
+#[derive(Deserialize)]
+struct Form {
+    payload: String,
+}
+
+#[derive(Deserialize)]
+struct Payload {
+    user: User,
+    #[serde(default)]
+    actions: Vec<Action>,
+    response_url: Option<String>,
+    message: Option<Message>,
+}
+
+#[derive(Deserialize)]
+struct User {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct Action {
+    action_id: String,
+    value: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    ts: String,
+}
+
+impl InteractionHandler {
+    /// Handle requests signed with the app's signing secret, muting callsites of the layer the
+    /// reload handle belongs to.
+    pub fn new(signing_secret: &str, reload_handle: ReloadHandle) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, signing_secret.as_bytes()),
+            reload_handle,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Verify and handle an interaction request. Acknowledgements and mutes are confirmed in the
+    /// thread of the message through the request's `response_url`.
+    pub async fn handle(&self, timestamp: &str, signature: &str, body: &str) -> Result<Interaction, InteractionError> {
+        self.verify(timestamp, signature, body, SystemTime::now())?;
+        let form: Form =
+            serde_urlencoded::from_str(body).map_err(|e| InteractionError::InvalidPayload(e.to_string()))?;
+        let payload: Payload =
+            serde_json::from_str(&form.payload).map_err(|e| InteractionError::InvalidPayload(e.to_string()))?;
+        let Some(action) = payload.actions.first() else {
+            return Ok(Interaction::Ignored);
+        };
+        let user = payload.user.id.clone();
+        let callsite = || {
+            action
+                .value
+                .clone()
+                .ok_or_else(|| InteractionError::InvalidPayload("the action has no callsite".to_string()))
+        };
+        let (interaction, confirmation) = match action.action_id.as_str() {
+            ACK_ACTION => (
+                Interaction::Acknowledged {
+                    user: user.clone(),
+                    callsite: callsite()?,
+                },
+                format!(":white_check_mark: Acknowledged by <@{}>", user),
+            ),
+            MUTE_ACTION => {
+                let callsite = callsite()?;
+                self.reload_handle.mute_callsite(&callsite, MUTE_DURATION);
+                (
+                    Interaction::Muted {
+                        user: user.clone(),
+                        callsite,
+                    },
+                    format!(":no_bell: Muted for 1h by <@{}>", user),
+                )
+            }
+            _ => return Ok(Interaction::Ignored),
+        };
+        if let Some(response_url) = &payload.response_url {
+            self.confirm(response_url, payload.message.as_ref(), confirmation).await;
+        }
+        Ok(interaction)
+    }
+
+    fn verify(&self, timestamp: &str, signature: &str, body: &str, now: SystemTime) -> Result<(), InteractionError> {
+        let signed_at: u64 = timestamp.parse().map_err(|_| InteractionError::Expired)?;
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        if now.abs_diff(signed_at) > MAX_REQUEST_AGE.as_secs() {
+            return Err(InteractionError::Expired);
+        }
+        let tag = signature
+            .strip_prefix("v0=")
+            .and_then(decode_hex)
+            .ok_or(InteractionError::InvalidSignature)?;
+        hmac::verify(&self.key, format!("v0:{}:{}", timestamp, body).as_bytes(), &tag)
+            .map_err(|_| InteractionError::InvalidSignature)
+    }
+
+    /// Reply in the message's thread. Failures are reported, since the interaction already took
+    /// effect.
+    async fn confirm(&self, response_url: &str, message: Option<&Message>, text: String) {
+        let body = serde_json::json!({
+            "response_type": "in_channel",
+            "replace_original": false,
+            "thread_ts": message.map(|message| message.ts.as_str()),
+            "text": text,
+        });
+        let result = self
+            .client
+            .post(response_url)
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        if let Err(e) = result {
+            println!("ERROR: failed to confirm slack interaction: {}", e);
+        }
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::{web_api::tests::StubApi, EventFilters, MessageFormat, SlackConfig, SlackLayer, SlackSettings};
+
+    const SECRET: &str = "8f742231b10e8888abcd99yyyzzz85a5";
+
+    fn sign(timestamp: &str, body: &str) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, SECRET.as_bytes());
+        let tag = hmac::sign(&key, format!("v0:{}:{}", timestamp, body).as_bytes());
+        let hex: String = tag.as_ref().iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("v0={}", hex)
+    }
+
+    fn now() -> String {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs().to_string()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn the_mute_button_mutes_the_callsite() {
+        let api = StubApi::default();
+        let url = api.start();
+        let (layer, worker) = SlackLayer::builder("test-app".into(), EventFilters::default())
+            .config(SlackConfig::web_api("xoxb-test", "C123").api_url(url.clone()))
+            .format(MessageFormat::Rich)
+            .platform_settings(SlackSettings::default().action_buttons())
+            .runbook_url("https://wiki.example.com/runbooks/{target}")
+            .build();
+        let handler = InteractionHandler::new(SECRET, layer.inner().reload_handle());
+        let dispatch = tracing::Dispatch::new(tracing_subscriber::registry().with(layer));
+        worker.start().await;
+        let fail = || tracing::dispatcher::with_default(&dispatch, || tracing::error!("payment failed"));
+
+        fail();
+        worker.flush().await;
+        let posted = api.requests.lock().unwrap()[0].body.clone();
+        let actions = posted["blocks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|block| block["type"] == "actions")
+            .unwrap();
+        let buttons: Vec<&str> = actions["elements"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|button| button["action_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(buttons, vec![ACK_ACTION, MUTE_ACTION, crate::RUNBOOK_ACTION]);
+        let callsite = actions["elements"][1]["value"].as_str().unwrap().to_string();
+
+        let payload = serde_json::json!({
+            "type": "block_actions",
+            "user": { "id": "U123" },
+            "actions": [{ "action_id": MUTE_ACTION, "value": callsite }],
+            "response_url": format!("{}/actions/T1", url),
+            "message": { "ts": "1700000000.000001" },
+        });
+        let body = serde_urlencoded::to_string([("payload", payload.to_string())]).unwrap();
+        let timestamp = now();
+        assert_eq!(
+            handler.handle(&timestamp, "v0=00", &body).await,
+            Err(InteractionError::InvalidSignature)
+        );
+        assert_eq!(
+            handler.handle("1531420618", &sign("1531420618", &body), &body).await,
+            Err(InteractionError::Expired)
+        );
+        assert_eq!(
+            handler.handle(&timestamp, &sign(&timestamp, &body), &body).await,
+            Ok(Interaction::Muted {
+                user: "U123".to_string(),
+                callsite,
+            })
+        );
+
+        fail();
+        worker.flush().await;
+        let requests = api.requests.lock().unwrap().clone();
+        let paths: Vec<&str> = requests.iter().map(|request| request.path.as_str()).collect();
+        assert_eq!(paths, vec!["/chat.postMessage", "/actions/T1"]);
+        assert_eq!(requests[1].body["thread_ts"], "1700000000.000001");
+        assert_eq!(requests[1].body["text"], ":no_bell: Muted for 1h by <@U123>");
+    }
+}
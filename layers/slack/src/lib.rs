@@ -1,11 +1,37 @@
 #![doc = include_str!("../README.md")]
 
+pub mod attachments;
+pub mod blocks;
+#[cfg(feature = "interactive")]
+pub mod interactive;
+mod settings;
+pub mod web_api;
+
 pub use tracing_layer_core::BackgroundWorker;
 pub use tracing_layer_core::layer::WebhookLayer;
 pub use tracing_layer_core::filters::EventFilters;
+pub use tracing_layer_core::config::{ConfigError, LayerConfig};
+pub use tracing_layer_core::MessageFormat;
+pub use settings::SlackSettings;
 use serde::Serialize;
+use crate::attachments::{Attachment, Field};
+use crate::blocks::{Block, ButtonStyle, Element, RichText, RichTextElement, Text};
+use crate::settings::MessageOptions;
 use tracing_layer_core::layer::WebhookLayerBuilder;
-use tracing_layer_core::{Config, WebhookMessage, WebhookMessageFactory, WebhookMessageInputs};
+use tracing_layer_core::otel::TraceContext;
+use tracing_layer_core::{
+    Config,
+    ConfigurableFactory,
+    EventError,
+    FromWebhookUrl,
+    LambdaInvocation,
+    Occurrences,
+    UploadFuture,
+    WebhookClient,
+    WebhookMessage,
+    WebhookMessageFactory,
+    WebhookMessageInputs,
+};
 
 /// Layer for forwarding tracing events to Slack.
 pub struct SlackLayer;
@@ -14,116 +40,656 @@ impl SlackLayer {
     pub fn builder(app_name: String, target_filters: EventFilters) -> WebhookLayerBuilder<SlackConfig, Self> {
         WebhookLayer::builder(app_name, target_filters)
     }
+
+    /// Create a builder from a declarative configuration, reporting every invalid setting at once.
+    pub fn builder_from_config(config: &LayerConfig) -> Result<WebhookLayerBuilder<SlackConfig, Self>, ConfigError> {
+        WebhookLayerBuilder::from_config(config)
+    }
+}
+
+/// Someone to notify of messages, see [`SlackSettings::mention`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mention {
+    /// The active members of the channel, `@here`.
+    Here,
+    /// Every member of the channel, `@channel`.
+    Channel,
+    /// A user group, by its id (`S...`).
+    UserGroup(String),
+    /// A user, by their id (`U...`).
+    User(String),
 }
 
+impl From<Mention> for String {
+    fn from(mention: Mention) -> Self {
+        match mention {
+            Mention::Here => "<!here>".to_string(),
+            Mention::Channel => "<!channel>".to_string(),
+            Mention::UserGroup(id) => format!("<!subteam^{}>", id),
+            Mention::User(id) => format!("<@{}>", id),
+        }
+    }
+}
+
+/// The `action_id` of the button acknowledging an event, added by
+/// [`SlackSettings::action_buttons`].
+pub const ACK_ACTION: &str = "tracing_layer_ack";
+/// The `action_id` of the button muting an event's callsite for [`MUTE_DURATION`].
+pub const MUTE_ACTION: &str = "tracing_layer_mute";
+/// The `action_id` of the button opening an event's runbook, see
+/// [`WebhookLayerBuilder::runbook_url`].
+pub const RUNBOOK_ACTION: &str = "tracing_layer_runbook";
+/// How long the mute button mutes a callsite for.
+pub const MUTE_DURATION: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// The layout used when the layer's builder does not request one.
+#[cfg(feature = "blocks")]
+const DEFAULT_FORMAT: MessageFormat = MessageFormat::Rich;
+#[cfg(not(feature = "blocks"))]
+const DEFAULT_FORMAT: MessageFormat = MessageFormat::PlainText;
+
 impl WebhookMessageFactory for SlackLayer {
     fn create(inputs: WebhookMessageInputs) -> impl WebhookMessage {
-        let target = inputs.target;
-        let span = inputs.span;
-        let metadata = inputs.metadata;
-        let message = inputs.message;
-        let app_name = inputs.app_name;
-        let source_file = inputs.source_file;
-        let source_line = inputs.source_line;
-        let event_level = inputs.event_level;
-
-        #[cfg(feature = "blocks")]
-        {
-            let event_level_emoji = match event_level {
-                tracing::Level::TRACE => ":mag:",
-                tracing::Level::DEBUG => ":bug:",
-                tracing::Level::INFO => ":information_source:",
-                tracing::Level::WARN => ":warning:",
-                tracing::Level::ERROR => ":x:",
-            };
-            let blocks = serde_json::json!([
-                {
-                    "type": "context",
-                    "elements": [
-                        {
-                            "type": "mrkdwn",
-                            "text": format!("{} - {} *{}*", app_name, event_level_emoji, event_level),
-                        }
-                    ]
-                },
-                {
-                    "type": "section",
-                    "text": {
-                        "type": "mrkdwn",
-                        "text": format!("\"_{}_\"", message),
-                    }
-                },
-                {
-                    "type": "section",
-                    "fields": [
-                        {
-                            "type": "mrkdwn",
-                            "text": format!("*Target Span*\n{}::{}", target, span)
-                        },
-                        {
-                            "type": "mrkdwn",
-                            "text": format!("*Source*\n{}#L{}", source_file, source_line)
-                        }
-                    ]
-                },
-                {
-                    "type": "section",
-                    "text": {
-                        "type": "mrkdwn",
-                        "text": "*Metadata:*"
-                    }
-                },
-                {
-                    "type": "section",
-                    "text": {
-                        "type": "mrkdwn",
-                        "text": format!("```\n{}\n```", metadata)
-                    }
-                }
-            ]);
-            let blocks_json = blocks.to_string();
-            SlackMessagePayload {
-                text: None,
-                blocks: Some(blocks_json),
-                webhook_url: inputs.webhook_url.to_string(),
+        let options = match inputs.platform.get::<SlackSettings>() {
+            Some(settings) => settings.options(&inputs),
+            None => SlackSettings::default().options(&inputs),
+        };
+        match inputs.format.unwrap_or(DEFAULT_FORMAT) {
+            MessageFormat::Rich => blocks_message(inputs, options),
+            MessageFormat::PlainText => text_message(inputs, options),
+            MessageFormat::Attachments => attachments_message(inputs, options),
+        }
+    }
+}
+
+impl ConfigurableFactory for SlackLayer {
+    type Settings = SlackSettings;
+}
+
+/// The mentions configured for the event, followed by a space, so that they lead the message and
+/// its notification.
+fn mentions_prefix(options: &MessageOptions) -> String {
+    options.mentions.iter().map(|mention| format!("{} ", mention)).collect()
+}
+
+fn level_emoji(level: tracing::Level) -> &'static str {
+    match level {
+        tracing::Level::TRACE => ":mag:",
+        tracing::Level::DEBUG => ":bug:",
+        tracing::Level::INFO => ":information_source:",
+        tracing::Level::WARN => ":warning:",
+        tracing::Level::ERROR => ":x:",
+    }
+}
+
+fn trace_text(trace: &TraceContext) -> String {
+    match &trace.url {
+        Some(url) => format!("<{}|{}>", url, trace.trace_id),
+        None => format!("`{}` / `{}`", trace.trace_id, trace.span_id),
+    }
+}
+
+fn lambda_text(lambda: &LambdaInvocation) -> String {
+    let request = match (&lambda.request_id, &lambda.log_url) {
+        (Some(request_id), Some(url)) => format!("\n<{}|{}>", url, request_id),
+        (Some(request_id), None) => format!("\n`{}`", request_id),
+        (None, _) => String::new(),
+    };
+    format!("`{}:{}`{}", lambda.function_name, lambda.function_version, request)
+}
+
+/// A numbered list of the sources of an error, under a heading.
+fn causes_text(error: &EventError) -> String {
+    let heading = if error.field == "error" {
+        "*Caused by*".to_string()
+    } else {
+        format!("*`{}` caused by*", error.field)
+    };
+    let causes: Vec<String> = error
+        .sources
+        .iter()
+        .enumerate()
+        .map(|(n, source)| format!("{}. {}", n + 1, source))
+        .collect();
+    format!("{}\n{}", heading, causes.join("\n"))
+}
+
+fn suppressed_text(suppressed_events: u64) -> String {
+    format!("_{} similar events were suppressed since the last message_", suppressed_events)
+}
+
+/// When the event happened, in the reader's time zone, and where.
+fn origin_text(inputs: &WebhookMessageInputs) -> String {
+    let mut origin = vec![format!(
+        "<!date^{}^{{date_short_pretty}} {{time_secs}}|{}>",
+        inputs.timestamp.timestamp(),
+        inputs.timestamp.to_rfc3339()
+    )];
+    origin.extend(inputs.context.iter().map(|(key, value)| format!("{}: *{}*", key, value)));
+    origin.join(" | ")
+}
+
+fn occurrences_text(occurrences: &Occurrences) -> String {
+    format!(
+        "Occurred *{}* times, last at <!date^{}^{{time}}|{}> `{}`",
+        occurrences.count,
+        occurrences.last.timestamp(),
+        occurrences.last.to_rfc3339(),
+        occurrences.sparkline()
+    )
+}
+
+/// Prefixed to formatting characters, so that they no longer open or close formatting.
+const ZERO_WIDTH_SPACE: char = '\u{200B}';
+
+/// Escape user-controlled text shown as mrkdwn, so that it is shown as logged.
+///
+/// Slack only supports escaping `&`, `<` and `>`, which also disarms mentions such as
+/// `<!channel>`. Formatting characters cannot be escaped, so they are defused instead.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '*' | '_' | '~' | '`' => {
+                escaped.push(ZERO_WIDTH_SPACE);
+                escaped.push(c);
             }
+            c => escaped.push(c),
         }
-        #[cfg(not(feature = "blocks"))]
-        {
-            let event_level = event.metadata().level().as_str();
-            let source_file = event.metadata().file().unwrap_or("Unknown");
-            let source_line = event.metadata().line().unwrap_or(0);
-            let payload = format!(
-                concat!(
-                    "*Trace from {}*\n",
-                    "*Event [{}]*: \"{}\"\n",
-                    "*Target*: _{}_\n",
-                    "*Span*: _{}_\n",
-                    "*Metadata*:\n",
-                    "```",
-                    "{}",
-                    "```\n",
-                    "*Source*: _{}#L{}_",
-                ),
-                app_name, event_level, message, span, target, metadata, source_file, source_line,
-            );
-            SlackMessagePayload {
-                text: Some(payload),
-                blocks: None,
-                webhook_url: webhook_url.to_string(),
+    }
+    escaped
+}
+
+/// Escape user-controlled text shown in a code span, where formatting characters are shown as
+/// they are and must not be defused, so that the text can be copied.
+fn escape_entities(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Escape user-controlled text shown in a code block, which only ends at three backticks.
+fn escape_code(text: &str) -> String {
+    escape_entities(text).replace("```", &format!("`{}``", ZERO_WIDTH_SPACE))
+}
+
+/// Escape the user-controlled text laid out as mrkdwn in every format. The origin of the event is
+/// shown as plain mrkdwn in blocks, but in code spans otherwise.
+fn escape_inputs(inputs: &mut WebhookMessageInputs, options: &mut MessageOptions, origin_in_code_spans: bool) {
+    let escape_origin = if origin_in_code_spans { escape_entities } else { escape };
+    inputs.app_name = escape(&inputs.app_name);
+    inputs.message = escape(&inputs.message);
+    inputs.target = escape_origin(&inputs.target);
+    inputs.span = escape_origin(&inputs.span);
+    inputs.source_file = escape_origin(&inputs.source_file);
+    inputs.metadata = escape_code(&inputs.metadata);
+    inputs.context = inputs
+        .context
+        .iter()
+        .map(|(key, value)| (escape(key), escape(value)))
+        .collect();
+    options.summary = options
+        .summary
+        .iter()
+        .map(|(name, value)| (name.clone(), escape(value)))
+        .collect();
+}
+
+/// Escape the error sources and backtrace, which are laid out as mrkdwn unless shown as rich text.
+fn escape_details(inputs: &mut WebhookMessageInputs) {
+    for error in &mut inputs.errors {
+        error.field = escape(&error.field);
+        error.sources = error.sources.iter().map(|source| escape(source)).collect();
+    }
+    inputs.backtrace = inputs.backtrace.as_deref().map(escape_code);
+}
+
+/// Slack rejects section text longer than this.
+const MAX_SECTION_CHARS: usize = 3000;
+/// Slack rejects section fields longer than this.
+const MAX_FIELD_CHARS: usize = 2000;
+/// Slack rejects messages with more blocks than this.
+const MAX_BLOCKS: usize = 50;
+/// Slack rejects sections with more fields than this.
+const MAX_FIELDS: usize = 10;
+/// The most errors whose sources are listed, so that the event's details still fit.
+const MAX_ERROR_BLOCKS: usize = 10;
+const TRUNCATION_MARKER: &str = "… _(truncated)_";
+
+/// The escapes added by [`escape`] and [`escape_code`], which a cut must not split apart.
+const ENTITIES: [&str; 3] = ["&amp;", "&lt;", "&gt;"];
+
+/// The length in bytes of the longest start of `text` with at most `max` characters which does not
+/// cut an escape in two: an entity such as `&amp;`, or a zero width space and the character it
+/// defuses.
+fn cut_at(text: &str, max: usize) -> usize {
+    let (mut len, mut chars) = (0, 0);
+    while let Some(c) = text[len..].chars().next() {
+        let rest = &text[len..];
+        let unit = match ENTITIES.iter().find(|entity| rest.starts_with(*entity)) {
+            Some(entity) => entity.len(),
+            None if c == ZERO_WIDTH_SPACE => {
+                c.len_utf8() + rest[c.len_utf8()..].chars().next().map_or(0, char::len_utf8)
+            }
+            None => c.len_utf8(),
+        };
+        let unit_chars = rest[..unit].chars().count();
+        if chars + unit_chars > max {
+            break;
+        }
+        len += unit;
+        chars += unit_chars;
+    }
+    len
+}
+
+/// Cut `text` down to `max` characters, ending with a visible marker when anything was cut.
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let kept = cut_at(text, max - TRUNCATION_MARKER.chars().count());
+    format!("{}{}", &text[..kept], TRUNCATION_MARKER)
+}
+
+/// The message in italic quotes, cut down to `max` characters. The text is cut before it is
+/// wrapped, so that the italics are always closed, and the marker follows outside of them.
+fn quoted_message(message: &str, max: usize) -> String {
+    if message.chars().count() + 4 <= max {
+        return format!("\"_{}_\"", message);
+    }
+    let kept = cut_at(message, max - 4 - TRUNCATION_MARKER.chars().count());
+    format!("\"_{}_\"{}", message[..kept].trim_end(), TRUNCATION_MARKER)
+}
+
+/// Split `text` into chunks of at most `max` characters, breaking between lines where possible.
+fn chunks(text: &str, max: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+    let mut chunk_len = 0;
+    for mut line in text.split_inclusive('\n') {
+        loop {
+            // The newline ending a chunk is dropped, so it does not count towards its length.
+            if chunk_len + line.trim_end_matches('\n').chars().count() <= max {
+                chunk.push_str(line);
+                chunk_len += line.chars().count();
+                break;
             }
+            if chunk_len > 0 {
+                chunks.push(chunk.trim_end_matches('\n').to_string());
+                chunk.clear();
+                chunk_len = 0;
+                continue;
+            }
+            // A single line longer than a chunk is split wherever it has to be, but between escapes.
+            let split = cut_at(line, max);
+            chunks.push(line[..split].to_string());
+            line = &line[split..];
         }
     }
+    if chunk_len > 0 {
+        chunks.push(chunk.trim_end_matches('\n').to_string());
+    }
+    chunks
+}
+
+/// A short summary of the event, shown in notifications and wherever blocks cannot be rendered.
+fn fallback_text(inputs: &WebhookMessageInputs) -> String {
+    format!(
+        "{} {} from {}: {}",
+        level_emoji(inputs.event_level),
+        inputs.event_level,
+        inputs.app_name,
+        truncate(&inputs.message, MAX_SECTION_CHARS)
+    )
+}
+
+/// The name large metadata is uploaded as.
+const METADATA_FILENAME: &str = "metadata.json";
+
+/// The metadata to upload as a file rather than show in the message, when it is longer than the
+/// configured size and the message is posted through the Web API.
+fn metadata_file(inputs: &WebhookMessageInputs, options: &MessageOptions) -> Option<String> {
+    let limit = options.upload_metadata_over?;
+    web_api_url(inputs.channel.as_deref(), &inputs.webhook_url)?;
+    (inputs.metadata.len() > limit).then(|| inputs.metadata.clone())
+}
+
+fn metadata_file_text() -> String {
+    format!("*Metadata:* uploaded to the thread as `{}`", METADATA_FILENAME)
+}
+
+fn blocks_message(mut inputs: WebhookMessageInputs, mut options: MessageOptions) -> SlackMessagePayload {
+    let metadata_file = metadata_file(&inputs, &options);
+    // Error sources and backtraces are laid out as rich text, which is never parsed as markup.
+    if inputs.escape_markup {
+        escape_inputs(&mut inputs, &mut options, false);
+    }
+    // The event's summary and origin always stay in the first message.
+    let mut head = vec![
+        Block::context(vec![Text::mrkdwn(format!(
+            "{}{} - {} *{}*",
+            mentions_prefix(&options),
+            inputs.app_name,
+            level_emoji(inputs.event_level),
+            inputs.event_level
+        ))]),
+        Block::section(Text::mrkdwn(quoted_message(&inputs.message, MAX_SECTION_CHARS))),
+    ];
+    // List the source chain of each error after the message.
+    let errors: Vec<_> = inputs.errors.iter().filter(|error| !error.sources.is_empty()).collect();
+    for error in errors.iter().take(MAX_ERROR_BLOCKS) {
+        let heading = if error.field == "error" {
+            RichText::bold("Caused by")
+        } else {
+            RichText::bold(format!("{} caused by", error.field))
+        };
+        let sources = error.sources.iter().map(|source| truncate(source, MAX_SECTION_CHARS));
+        head.push(Block::rich_text(vec![
+            RichTextElement::RichTextSection { elements: vec![heading] },
+            RichTextElement::ordered_list(sources),
+        ]));
+    }
+    if errors.len() > MAX_ERROR_BLOCKS {
+        head.push(Block::context(vec![Text::mrkdwn(format!(
+            "_The sources of {} more errors are not shown_",
+            errors.len() - MAX_ERROR_BLOCKS
+        ))]));
+    }
+    let mut fields = vec![
+        format!("*Target Span*\n{}::{}", inputs.target, inputs.span),
+        format!("*Source*\n{}#L{}", inputs.source_file, inputs.source_line),
+    ];
+    if let Some(trace) = &inputs.trace {
+        fields.push(format!("*Trace*\n{}", trace_text(trace)));
+    }
+    if let Some(lambda) = &inputs.lambda {
+        fields.push(format!("*Lambda*\n{}", lambda_text(lambda)));
+    }
+    fields.extend(options.summary.iter().map(|(name, value)| format!("*{}*\n{}", name, value)));
+    head.push(Block::fields(
+        fields
+            .iter()
+            .take(MAX_FIELDS)
+            .map(|field| Text::mrkdwn(truncate(field, MAX_FIELD_CHARS)))
+            .collect(),
+    ));
+
+    // Long metadata is split over several sections, each wrapped in its own code block.
+    const FENCE: &str = "```\n\n```";
+    let mut body = Vec::new();
+    match metadata_file {
+        Some(_) => body.push(Block::section(Text::mrkdwn(metadata_file_text()))),
+        None => {
+            body.push(Block::section(Text::mrkdwn("*Metadata:*")));
+            body.extend(
+                chunks(&inputs.metadata, MAX_SECTION_CHARS - FENCE.len())
+                    .into_iter()
+                    .map(|chunk| Block::section(Text::mrkdwn(format!("```\n{}\n```", chunk)))),
+            );
+        }
+    }
+    // Slack collapses long sections behind "Show more", so the backtrace goes last.
+    if let Some(backtrace) = &inputs.backtrace {
+        body.push(Block::section(Text::mrkdwn("*Backtrace:*")));
+        body.push(Block::rich_text(vec![RichTextElement::preformatted(truncate(
+            backtrace,
+            MAX_SECTION_CHARS,
+        ))]));
+    }
+
+    let mut tail = Vec::new();
+    if inputs.suppressed_events > 0 {
+        tail.push(Block::context(vec![Text::mrkdwn(suppressed_text(inputs.suppressed_events))]));
+    }
+    let actions = action_elements(&inputs, &options);
+    if !actions.is_empty() {
+        tail.push(Block::actions(actions));
+    }
+    tail.push(Block::Divider);
+    tail.push(Block::context(vec![Text::mrkdwn(origin_text(&inputs))]));
+
+    // Blocks that do not fit are moved to follow-up messages, and a note takes their place.
+    let text = fallback_text(&inputs);
+    let mut follow_ups = Vec::new();
+    if head.len() + body.len() + tail.len() > MAX_BLOCKS {
+        let overflow = body.split_off(MAX_BLOCKS.saturating_sub(head.len() + tail.len() + 1));
+        body.push(Block::context(vec![Text::mrkdwn(
+            "_The rest of this event is continued in the next message_",
+        )]));
+        follow_ups = overflow
+            .chunks(MAX_BLOCKS)
+            .map(|blocks| SlackMessagePayload {
+                channel: inputs.channel.clone(),
+                thread_ts: None,
+                reply_broadcast: false,
+                text: format!("{} (continued)", text),
+                blocks: Some(blocks.to_vec()),
+                attachments: None,
+                follow_ups: Vec::new(),
+                thread_key: None,
+                broadcast_reply: false,
+                update_key: None,
+                metadata_file: None,
+                webhook_url: inputs.webhook_url.clone(),
+            })
+            .collect();
+    }
+    let mut blocks = head;
+    blocks.extend(body);
+    blocks.extend(tail);
+    // Follow-ups do not repeat the mentions, so that nobody is notified twice.
+    let text = format!("{}{}", mentions_prefix(&options), text);
+    SlackMessagePayload {
+        channel: inputs.channel,
+        thread_ts: None,
+        reply_broadcast: false,
+        text,
+        blocks: Some(blocks),
+        attachments: None,
+        follow_ups,
+        thread_key: inputs.thread_key,
+        broadcast_reply: options.broadcast_reply,
+        update_key: options.update_key,
+        metadata_file,
+        webhook_url: inputs.webhook_url,
+    }
+}
+
+fn text_message(mut inputs: WebhookMessageInputs, mut options: MessageOptions) -> SlackMessagePayload {
+    let metadata_file = metadata_file(&inputs, &options);
+    if inputs.escape_markup {
+        escape_inputs(&mut inputs, &mut options, true);
+        escape_details(&mut inputs);
+    }
+    let mut lines = vec![
+        format!(
+            "{}{} *{}* from *{}*",
+            mentions_prefix(&options),
+            level_emoji(inputs.event_level),
+            inputs.event_level,
+            inputs.app_name
+        ),
+        format!(">_{}_", inputs.message),
+        format!("*Target Span*: `{}::{}`", inputs.target, inputs.span),
+        format!("*Source*: `{}#L{}`", inputs.source_file, inputs.source_line),
+    ];
+    if let Some(trace) = &inputs.trace {
+        lines.push(format!("*Trace*: {}", trace_text(trace)));
+    }
+    if let Some(lambda) = &inputs.lambda {
+        lines.push(format!("*Lambda*: {}", lambda_text(lambda).replace('\n', " ")));
+    }
+    for error in inputs.errors.iter().filter(|error| !error.sources.is_empty()) {
+        lines.push(causes_text(error));
+    }
+    match metadata_file {
+        Some(_) => lines.push(metadata_file_text()),
+        None => lines.push(format!("*Metadata*:\n```\n{}\n```", inputs.metadata)),
+    }
+    if let Some(backtrace) = &inputs.backtrace {
+        lines.push(format!("*Backtrace*:\n```\n{}\n```", backtrace));
+    }
+    if inputs.suppressed_events > 0 {
+        lines.push(suppressed_text(inputs.suppressed_events));
+    }
+    if let Some(url) = &inputs.runbook_url {
+        lines.push(format!("*Runbook*: <{}>", url));
+    }
+    lines.push(origin_text(&inputs));
+    SlackMessagePayload {
+        channel: inputs.channel,
+        thread_ts: None,
+        reply_broadcast: false,
+        text: lines.join("\n"),
+        blocks: None,
+        attachments: None,
+        follow_ups: Vec::new(),
+        thread_key: inputs.thread_key,
+        broadcast_reply: options.broadcast_reply,
+        update_key: options.update_key,
+        metadata_file,
+        webhook_url: inputs.webhook_url,
+    }
+}
+
+/// The buttons of a message, as configured on the layer's builder. Acknowledging and muting send
+/// the event's callsite with their interaction requests.
+fn action_elements(inputs: &WebhookMessageInputs, options: &MessageOptions) -> Vec<Element> {
+    let mut elements = Vec::new();
+    if options.action_buttons {
+        elements.push(Element::button("Ack", ACK_ACTION, &inputs.callsite).styled(ButtonStyle::Primary));
+        elements.push(Element::button("Mute for 1h", MUTE_ACTION, &inputs.callsite).styled(ButtonStyle::Danger));
+    }
+    if let Some(url) = &inputs.runbook_url {
+        elements.push(Element::link_button("Runbook", RUNBOOK_ACTION, url));
+    }
+    elements
+}
+
+/// The color of the bar of attachments for each level, unless configured on the layer's builder.
+fn level_color(level: tracing::Level) -> u32 {
+    match level {
+        tracing::Level::TRACE | tracing::Level::DEBUG => 0x1abc9c,
+        tracing::Level::INFO => 0x57f287,
+        tracing::Level::WARN => 0xe67e22,
+        tracing::Level::ERROR => 0xed4245,
+    }
+}
+
+fn attachments_message(mut inputs: WebhookMessageInputs, mut options: MessageOptions) -> SlackMessagePayload {
+    let metadata_file = metadata_file(&inputs, &options);
+    if inputs.escape_markup {
+        escape_inputs(&mut inputs, &mut options, true);
+        escape_details(&mut inputs);
+    }
+    // The summary leads the message, and the event's details follow in the colored attachment.
+    let text = format!(
+        "{}{} *{}* from *{}*",
+        mentions_prefix(&options),
+        level_emoji(inputs.event_level),
+        inputs.event_level,
+        inputs.app_name
+    );
+
+    let mut lines = vec![format!(">_{}_", inputs.message)];
+    for error in inputs.errors.iter().filter(|error| !error.sources.is_empty()) {
+        lines.push(causes_text(error));
+    }
+    match metadata_file {
+        Some(_) => lines.push(metadata_file_text()),
+        None => lines.push(format!("*Metadata*:\n```\n{}\n```", inputs.metadata)),
+    }
+    if let Some(backtrace) = &inputs.backtrace {
+        lines.push(format!("*Backtrace*:\n```\n{}\n```", backtrace));
+    }
+
+    let mut fields = vec![
+        Field::short("Target Span", format!("`{}::{}`", inputs.target, inputs.span)),
+        Field::short("Source", format!("`{}#L{}`", inputs.source_file, inputs.source_line)),
+    ];
+    if let Some(trace) = &inputs.trace {
+        fields.push(Field::short("Trace", trace_text(trace)));
+    }
+    if let Some(lambda) = &inputs.lambda {
+        fields.push(Field::short("Lambda", lambda_text(lambda)));
+    }
+    fields.extend(options.summary.iter().map(|(name, value)| Field::short(name, value)));
+    if let Some(url) = &inputs.runbook_url {
+        fields.push(Field::short("Runbook", format!("<{}|Open runbook>", url)));
+    }
+    if inputs.suppressed_events > 0 {
+        fields.push(Field::long("Suppressed", suppressed_text(inputs.suppressed_events)));
+    }
+
+    // The footer names the application and the context it runs in, unless replaced.
+    let footer = options.footer.clone().unwrap_or_else(|| {
+        std::iter::once(inputs.app_name.clone())
+            .chain(inputs.context.iter().map(|(key, value)| format!("{}: {}", key, value)))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    });
+    let attachment = Attachment {
+        fallback: fallback_text(&inputs),
+        color: attachments::color(options.color.unwrap_or_else(|| level_color(inputs.event_level))),
+        text: lines.join("\n"),
+        fields,
+        footer: Some(footer),
+        ts: Some(inputs.timestamp.timestamp()),
+        mrkdwn_in: vec!["text", "fields"],
+    };
+    SlackMessagePayload {
+        channel: inputs.channel,
+        thread_ts: None,
+        reply_broadcast: false,
+        text,
+        blocks: None,
+        attachments: Some(vec![attachment]),
+        follow_ups: Vec::new(),
+        thread_key: inputs.thread_key,
+        broadcast_reply: options.broadcast_reply,
+        update_key: options.update_key,
+        metadata_file,
+        webhook_url: inputs.webhook_url,
+    }
 }
 
 /// The message sent to Slack. The logged record being "drained" will be
 /// converted into this format.
+///
+/// `text` is always set: it is the whole message without blocks or attachments, the notification
+/// text with blocks, and a summary shown above attachments.
 #[derive(Debug, Clone, Serialize)]
 pub(crate) struct SlackMessagePayload {
+    /// The channel to post to, when posting through the Web API.
     #[serde(skip_serializing_if = "Option::is_none")]
-    text: Option<String>,
+    channel: Option<String>,
+    /// The message this one replies to, in its thread.
     #[serde(skip_serializing_if = "Option::is_none")]
-    blocks: Option<String>,
+    thread_ts: Option<String>,
+    /// Whether a reply is also shown in the channel.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    reply_broadcast: bool,
+    text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blocks: Option<Vec<Block>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attachments: Option<Vec<Attachment>>,
+    /// Blocks that did not fit into this message, sent right after it.
+    #[serde(skip_serializing)]
+    follow_ups: Vec<SlackMessagePayload>,
+    /// Relates this message to earlier ones, which are threaded when posting through the Web API.
+    #[serde(skip_serializing)]
+    thread_key: Option<String>,
+    #[serde(skip_serializing)]
+    broadcast_reply: bool,
+    /// Relates this message to earlier duplicates, which update it when posting through the Web API.
+    #[serde(skip_serializing)]
+    update_key: Option<String>,
+    /// Metadata too large to show in the message, uploaded into its thread as a file.
+    #[serde(skip_serializing)]
+    metadata_file: Option<String>,
     #[serde(skip_serializing)]
     webhook_url: String,
 }
@@ -134,27 +700,183 @@ impl WebhookMessage for SlackMessagePayload {
     }
 
     fn serialize(&self) -> String {
-        serde_json::to_string(self).expect("failed to serialize slack message")
+        // A config for the Web API may be rerouted to an incoming webhook, which posts to its own
+        // channel and knows nothing of threads.
+        let payload = match self.api_url() {
+            Some(_) => serde_json::to_string(self),
+            None => serde_json::to_string(&SlackMessagePayload {
+                channel: None,
+                thread_ts: None,
+                reply_broadcast: false,
+                ..self.clone()
+            }),
+        };
+        payload.expect("failed to serialize slack message")
+    }
+
+    fn follow_ups(&self) -> Vec<String> {
+        self.follow_ups.iter().map(WebhookMessage::serialize).collect()
+    }
+
+    fn check_response(&self, body: &str) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        // Incoming webhooks answer with plain text, only the Web API with JSON, so this depends on
+        // where the message was posted, which a reload or reroute may change. Updates need the
+        // channel's id, which may differ from the configured name, so the reference carries both.
+        match self.api_url() {
+            Some(_) => {
+                let posted = web_api::parse_response(body)?;
+                Ok(Some(format!("{} {}", posted.channel, posted.ts)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn channel(&self) -> Option<&str> {
+        self.channel.as_deref()
+    }
+
+    fn thread_key(&self) -> Option<&str> {
+        // Incoming webhooks cannot reply in threads.
+        self.api_url().and(self.thread_key.as_deref())
+    }
+
+    fn serialize_reply(&self, reference: &str) -> String {
+        let (_, ts) = split_reference(reference);
+        WebhookMessage::serialize(&SlackMessagePayload {
+            thread_ts: Some(ts.to_string()),
+            reply_broadcast: self.broadcast_reply,
+            ..self.clone()
+        })
+    }
+
+    fn serialize_follow_up_replies(&self, reference: &str) -> Vec<String> {
+        self.follow_ups
+            .iter()
+            .map(|follow_up| follow_up.serialize_reply(reference))
+            .collect()
     }
+
+    fn update_key(&self) -> Option<&str> {
+        // Incoming webhooks cannot update their messages.
+        self.api_url().and(self.update_key.as_deref())
+    }
+
+    /// Update this message with its content unchanged, its origin followed by how often it occurred.
+    fn serialize_update(&self, reference: &str, occurrences: &Occurrences) -> Option<(String, String)> {
+        let url = format!("{}/chat.update", self.api_url()?);
+        let (channel, ts) = split_reference(reference);
+        let mut update = SlackMessagePayload {
+            channel: Some(channel.to_string()),
+            thread_ts: None,
+            reply_broadcast: false,
+            ..self.clone()
+        };
+        let occurred = occurrences_text(occurrences);
+        match update.blocks.as_mut().and_then(|blocks| blocks.last_mut()) {
+            Some(Block::Context { elements }) => elements.push(Text::mrkdwn(occurred)),
+            _ => update.text = format!("{}\n{}", update.text, occurred),
+        }
+        let mut body = serde_json::to_value(&update).expect("failed to serialize slack message");
+        body["ts"] = ts.into();
+        Some((url, body.to_string()))
+    }
+
+    fn upload_attachments<'a>(&'a self, client: &'a WebhookClient, reference: &'a str) -> Option<UploadFuture<'a>> {
+        let (api_url, content) = (self.api_url()?, self.metadata_file.as_deref()?);
+        let (channel, ts) = split_reference(reference);
+        let thread = web_api::PostedMessage {
+            channel: channel.to_string(),
+            ts: ts.to_string(),
+        };
+        Some(Box::pin(async move {
+            web_api::upload_file(client, api_url, &thread, METADATA_FILENAME, content).await?;
+            Ok(())
+        }))
+    }
+}
+
+impl SlackMessagePayload {
+    /// The base URL of the Web API, when posting through it.
+    fn api_url(&self) -> Option<&str> {
+        web_api_url(self.channel.as_deref(), &self.webhook_url)
+    }
+}
+
+/// The base URL of the Web API, when a message for `channel` is posted to `webhook_url` through
+/// it rather than to an incoming webhook, e.g. one it was rerouted to.
+fn web_api_url<'a>(channel: Option<&str>, webhook_url: &'a str) -> Option<&'a str> {
+    channel?;
+    webhook_url.strip_suffix("/chat.postMessage")
+}
+
+/// Split a reference returned by `check_response` into the message's channel id and `ts`.
+fn split_reference(reference: &str) -> (&str, &str) {
+    reference.split_once(' ').unwrap_or(("", reference))
 }
 
-/// Configuration describing how to forward tracing events to Slack.
+/// Configuration describing how to forward tracing events to Slack, either through an incoming
+/// webhook or through the Web API with a bot token.
 pub struct SlackConfig {
     pub(crate) webhook_url: String,
+    pub(crate) bot: Option<BotConfig>,
+}
+
+/// The credentials and default channel used to post through the Web API.
+pub(crate) struct BotConfig {
+    token: String,
+    channel: String,
 }
 
 impl SlackConfig {
     pub fn new(webhook_url: String) -> Self {
-        Self { webhook_url }
+        Self {
+            webhook_url,
+            bot: None,
+        }
+    }
+
+    /// Post messages through the Web API's `chat.postMessage` with a bot token (`xoxb-...`), to
+    /// the given channel by default. The bot must be a member of every channel it posts to.
+    ///
+    /// Use [`WebhookLayerBuilder::channel_field`] to choose the channel per message.
+    pub fn web_api(token: impl Into<String>, channel: impl Into<String>) -> Self {
+        Self {
+            webhook_url: format!("{}/chat.postMessage", web_api::DEFAULT_API_URL),
+            bot: Some(BotConfig {
+                token: token.into(),
+                channel: channel.into(),
+            }),
+        }
+    }
+
+    /// Send Web API requests to another base URL than `https://slack.com/api`, e.g. a local
+    /// stub of the API in tests.
+    pub fn api_url(mut self, api_url: impl AsRef<str>) -> Self {
+        self.webhook_url = format!("{}/chat.postMessage", api_url.as_ref().trim_end_matches('/'));
+        self
     }
 
     /// Create a new config for forwarding messages to Slack using configuration
     /// available in the environment.
     ///
+    /// Messages are posted through the Web API if a bot token is set, otherwise through an
+    /// incoming webhook.
+    ///
     /// Required env vars:
+    ///   * SLACK_BOT_TOKEN and SLACK_CHANNEL, or
     ///   * SLACK_WEBHOOK_URL
+    ///
+    /// A bot token without a channel is reported and ignored, falling back to the webhook.
     pub fn new_from_env() -> Self {
-        Self::new(std::env::var("SLACK_WEBHOOK_URL").expect("slack webhook url in env"))
+        match (std::env::var("SLACK_BOT_TOKEN"), std::env::var("SLACK_CHANNEL")) {
+            (Ok(token), Ok(channel)) => Self::web_api(token, channel),
+            (token, _) => {
+                if token.is_ok() {
+                    println!("ERROR: SLACK_BOT_TOKEN is set without SLACK_CHANNEL, posting to SLACK_WEBHOOK_URL");
+                }
+                Self::new(std::env::var("SLACK_WEBHOOK_URL").expect("slack webhook url in env"))
+            }
+        }
     }
 }
 
@@ -164,6 +886,12 @@ impl Default for SlackConfig {
     }
 }
 
+impl FromWebhookUrl for SlackConfig {
+    fn new_from_webhook_url(webhook_url: String) -> Self {
+        Self::new(webhook_url)
+    }
+}
+
 impl Config for SlackConfig {
     fn webhook_url(&self) -> &str {
         &self.webhook_url
@@ -172,9 +900,347 @@ impl Config for SlackConfig {
     fn new_from_env() -> Self where Self: Sized {
         Self::new_from_env()
     }
+
+    fn headers(&self) -> Vec<(String, String)> {
+        match &self.bot {
+            Some(bot) => vec![("Authorization".to_string(), format!("Bearer {}", bot.token))],
+            None => Vec::new(),
+        }
+    }
+
+    fn api_url(&self) -> &str {
+        // Without a bot, there is no token and the webhook is the only URL.
+        match &self.bot {
+            Some(_) => self.webhook_url.strip_suffix("/chat.postMessage").unwrap_or(&self.webhook_url),
+            None => &self.webhook_url,
+        }
+    }
+
+    fn channel(&self) -> Option<&str> {
+        self.bot.as_ref().map(|bot| bot.channel.as_str())
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use serde_json::Value;
+    use tracing_layer_core::PlatformSettings;
+
+    use super::*;
+
+    fn inputs() -> WebhookMessageInputs {
+        WebhookMessageInputs {
+            app_name: "test-app".into(),
+            message: "failed to load user".into(),
+            target: "users".into(),
+            span: "load".into(),
+            metadata: "{}".into(),
+            webhook_url: "http://localhost".into(),
+            source_line: 1,
+            source_file: "src/main.rs".into(),
+            event_level: tracing::Level::ERROR,
+            errors: vec![EventError {
+                field: "error".into(),
+                message: "failed to load user".into(),
+                sources: vec!["query failed".into(), "connection reset".into()],
+            }],
+            timestamp: chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            context: std::collections::BTreeMap::from([
+                ("env".to_string(), "prod".to_string()),
+                ("host".to_string(), "web-1".to_string()),
+            ]),
+            callsite: "0123456789abcdef".into(),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(feature = "blocks")]
+    #[test]
+    fn error_sources_are_listed() {
+        let payload: Value = serde_json::from_str(&SlackLayer::create(inputs()).serialize()).unwrap();
+        let blocks = &payload["blocks"];
+        assert_eq!(blocks[2]["elements"][0]["elements"][0]["text"], "Caused by");
+        let causes = &blocks[2]["elements"][1];
+        assert_eq!(causes["style"], "ordered");
+        assert_eq!(causes["elements"][0]["elements"][0]["text"], "query failed");
+        assert_eq!(causes["elements"][1]["elements"][0]["text"], "connection reset");
+    }
+
+    #[cfg(feature = "blocks")]
+    #[test]
+    fn trace_links_to_the_trace_viewer() {
+        let mut inputs = inputs();
+        inputs.trace = Some(TraceContext {
+            trace_id: "4bf92f3577b34da6a3ce929d0e0e4736".into(),
+            span_id: "00f067aa0ba902b7".into(),
+            url: Some("https://traces.example.com/4bf92f3577b34da6a3ce929d0e0e4736".into()),
+        });
+        let payload: Value = serde_json::from_str(&SlackLayer::create(inputs).serialize()).unwrap();
+        let blocks = &payload["blocks"];
+        assert_eq!(
+            blocks[3]["fields"][2]["text"],
+            "*Trace*\n<https://traces.example.com/4bf92f3577b34da6a3ce929d0e0e4736|4bf92f3577b34da6a3ce929d0e0e4736>"
+        );
+    }
+
+    #[cfg(feature = "blocks")]
+    #[test]
+    fn lambda_request_links_to_its_logs() {
+        let mut inputs = inputs();
+        inputs.lambda = Some(LambdaInvocation {
+            request_id: Some("req-1".into()),
+            function_name: "billing".into(),
+            function_version: "7".into(),
+            log_url: Some("https://console.aws.amazon.com/logs".into()),
+        });
+        let payload: Value = serde_json::from_str(&SlackLayer::create(inputs).serialize()).unwrap();
+        let blocks = &payload["blocks"];
+        assert_eq!(
+            blocks[3]["fields"][2]["text"],
+            "*Lambda*\n`billing:7`\n<https://console.aws.amazon.com/logs|req-1>"
+        );
+    }
+
+    #[cfg(feature = "blocks")]
+    #[test]
+    fn blocks_are_an_array_with_a_text_fallback() {
+        let payload: Value = serde_json::from_str(&SlackLayer::create(inputs()).serialize()).unwrap();
+        assert!(payload["blocks"].is_array());
+        assert_eq!(payload["text"], ":x: ERROR from test-app: failed to load user");
+    }
+
+    #[test]
+    fn long_lines_are_split_and_short_ones_kept_together() {
+        assert_eq!(chunks("ab\ncd\nefghij", 5), vec!["ab\ncd", "efghi", "j"]);
+    }
+
+    #[cfg(feature = "blocks")]
+    #[test]
+    fn long_metadata_is_split_into_sections() {
+        let mut inputs = inputs();
+        inputs.message = "x".repeat(4000);
+        inputs.metadata = "{\n".to_string() + &"  \"key\": \"value\",\n".repeat(500) + "}";
+        let message = SlackLayer::create(inputs);
+        assert!(message.follow_ups().is_empty());
+        let payload: Value = serde_json::from_str(&message.serialize()).unwrap();
+        let texts: Vec<&str> = payload["blocks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|block| block["text"]["text"].as_str())
+            .collect();
+        assert!(texts.iter().all(|text| text.chars().count() <= MAX_SECTION_CHARS));
+        assert!(texts[0].ends_with(TRUNCATION_MARKER));
+        assert_eq!(texts.iter().filter(|text| text.starts_with("```")).count(), 4);
+    }
+
+    #[cfg(feature = "blocks")]
+    #[test]
+    fn blocks_past_the_limit_are_sent_as_follow_ups() {
+        let mut inputs = inputs();
+        inputs.metadata = "x\n".repeat(1500 * 60);
+        let message = SlackLayer::create(inputs);
+        let payload: Value = serde_json::from_str(&message.serialize()).unwrap();
+        let blocks = payload["blocks"].as_array().unwrap();
+        assert_eq!(blocks.len(), MAX_BLOCKS);
+        assert_eq!(
+            blocks[blocks.len() - 3]["elements"][0]["text"],
+            "_The rest of this event is continued in the next message_"
+        );
+        assert_eq!(blocks[blocks.len() - 1]["type"], "context");
 
+        let follow_ups: Vec<Value> = message
+            .follow_ups()
+            .iter()
+            .map(|follow_up| serde_json::from_str(follow_up).unwrap())
+            .collect();
+        assert_eq!(follow_ups.len(), 1);
+        assert_eq!(follow_ups[0]["text"], ":x: ERROR from test-app: failed to load user (continued)");
+        let sections = blocks.len() - 8 + follow_ups[0]["blocks"].as_array().unwrap().len();
+        assert_eq!(sections, 61);
+    }
+
+    #[cfg(feature = "blocks")]
+    #[test]
+    fn many_errors_fit_into_the_block_limit() {
+        let mut inputs = inputs();
+        inputs.metadata = "x\n".repeat(1500 * 60);
+        inputs.errors = (0..60)
+            .map(|index| EventError {
+                field: format!("error_{}", index),
+                message: "failed".into(),
+                sources: vec!["connection reset".into()],
+            })
+            .collect();
+        let message = SlackLayer::create(inputs);
+        let payload: Value = serde_json::from_str(&message.serialize()).unwrap();
+        let blocks = payload["blocks"].as_array().unwrap();
+        assert_eq!(blocks.len(), MAX_BLOCKS);
+        let rich_text = blocks.iter().filter(|block| block["type"] == "rich_text").count();
+        assert_eq!(rich_text, MAX_ERROR_BLOCKS);
+        assert_eq!(
+            blocks[2 + MAX_ERROR_BLOCKS]["elements"][0]["text"],
+            "_The sources of 50 more errors are not shown_"
+        );
+        for follow_up in message.follow_ups() {
+            let follow_up: Value = serde_json::from_str(&follow_up).unwrap();
+            assert!(follow_up["blocks"].as_array().unwrap().len() <= MAX_BLOCKS);
+        }
+    }
+
+    #[cfg(feature = "blocks")]
+    #[test]
+    fn long_messages_stay_italic() {
+        let mut inputs = inputs();
+        inputs.message = "a".repeat(MAX_SECTION_CHARS);
+        let payload: Value = serde_json::from_str(&SlackLayer::create(inputs).serialize()).unwrap();
+        let text = payload["blocks"][1]["text"]["text"].as_str().unwrap();
+        assert_eq!(text.chars().count(), MAX_SECTION_CHARS);
+        assert!(text.starts_with("\"_a"));
+        assert!(text.ends_with(&format!("a_\"{}", TRUNCATION_MARKER)));
+    }
+
+    #[test]
+    fn cuts_keep_escapes_whole() {
+        let ampersands = escape(&"&".repeat(1000));
+        let text = truncate(&ampersands, MAX_SECTION_CHARS);
+        assert!(text.chars().count() <= MAX_SECTION_CHARS);
+        assert!(!text.replace("&amp;", "").contains('&'));
+
+        let text = quoted_message(&escape(&"_".repeat(MAX_SECTION_CHARS)), MAX_SECTION_CHARS);
+        assert!(text.ends_with(&format!("\u{200B}__\"{}", TRUNCATION_MARKER)));
+
+        let metadata = escape_code(&format!("{}<{}", "a".repeat(2990), "&".repeat(2000)));
+        let chunks = chunks(&metadata, MAX_SECTION_CHARS - 8);
+        assert!(chunks.len() > 2);
+        for chunk in &chunks {
+            assert!(chunk.chars().count() <= MAX_SECTION_CHARS - 8);
+            assert!(!chunk.replace("&amp;", "").replace("&lt;", "").contains('&'));
+        }
+        assert_eq!(chunks.concat(), metadata);
+    }
+
+    #[test]
+    fn markup_is_escaped() {
+        assert_eq!(
+            escape("<!channel> *now* & `x`"),
+            "&lt;!channel&gt; \u{200B}*now\u{200B}* &amp; \u{200B}`x\u{200B}`"
+        );
+        assert_eq!(escape_code("<a>```"), "&lt;a&gt;`\u{200B}``");
+
+        let mut inputs = inputs();
+        inputs.format = Some(MessageFormat::PlainText);
+        inputs.message = "<!channel> deploy_failed".into();
+        let payload: Value = serde_json::from_str(&SlackLayer::create(inputs).serialize()).unwrap();
+        assert!(payload["text"].as_str().unwrap().contains(">_&lt;!channel&gt; deploy\u{200B}_failed_"));
+    }
+
+    #[test]
+    fn code_spans_are_not_defused() {
+        let mut inputs = inputs();
+        inputs.format = Some(MessageFormat::PlainText);
+        inputs.target = "jobs::send_mail".into();
+        inputs.source_file = "src/send_mail.rs".into();
+        let payload: Value = serde_json::from_str(&SlackLayer::create(inputs).serialize()).unwrap();
+        let text = payload["text"].as_str().unwrap();
+        assert!(text.contains("*Target Span*: `jobs::send_mail::load`"));
+        assert!(text.contains("*Source*: `src/send_mail.rs#L1`"));
+        assert!(!text.contains(ZERO_WIDTH_SPACE));
+    }
+
+    #[test]
+    fn escaping_can_be_disabled() {
+        let mut inputs = inputs();
+        inputs.format = Some(MessageFormat::PlainText);
+        inputs.message = "<!channel> deploy_failed".into();
+        inputs.escape_markup = false;
+        let payload: Value = serde_json::from_str(&SlackLayer::create(inputs).serialize()).unwrap();
+        assert!(payload["text"].as_str().unwrap().contains(">_<!channel> deploy_failed_"));
+    }
+
+    #[test]
+    fn mentions_lead_the_message() {
+        let settings = SlackSettings::default()
+            .mention(tracing::Level::ERROR, Mention::Here)
+            .mention(tracing::Level::WARN, Mention::UserGroup("S123".into()));
+        let mut rich = inputs();
+        rich.format = Some(MessageFormat::Rich);
+        rich.platform = PlatformSettings::new(settings);
+        let payload: Value = serde_json::from_str(&SlackLayer::create(rich).serialize()).unwrap();
+        assert_eq!(
+            payload["text"],
+            "<!here> <!subteam^S123> :x: ERROR from test-app: failed to load user"
+        );
+        assert_eq!(
+            payload["blocks"][0]["elements"][0]["text"],
+            "<!here> <!subteam^S123> test-app - :x: *ERROR*"
+        );
+
+        let mut plain = inputs();
+        plain.format = Some(MessageFormat::PlainText);
+        let settings = SlackSettings::default().mention(tracing::Level::ERROR, Mention::User("U42".into()));
+        plain.platform = PlatformSettings::new(settings);
+        let payload: Value = serde_json::from_str(&SlackLayer::create(plain).serialize()).unwrap();
+        assert!(payload["text"].as_str().unwrap().starts_with("<@U42> :x: *ERROR* from *test-app*\n"));
+    }
+
+    #[test]
+    fn attachments_are_colored_by_level() {
+        let mut inputs = inputs();
+        inputs.format = Some(MessageFormat::Attachments);
+        inputs.metadata = r#"{ "user_id": 42 }"#.into();
+        inputs.platform = PlatformSettings::new(SlackSettings::default().summary_field("user_id"));
+        let payload: Value = serde_json::from_str(&SlackLayer::create(inputs).serialize()).unwrap();
+        assert!(payload.get("blocks").is_none());
+        assert_eq!(payload["text"], ":x: *ERROR* from *test-app*");
+        let attachment = &payload["attachments"][0];
+        assert_eq!(attachment["color"], "#ed4245");
+        assert_eq!(attachment["fallback"], ":x: ERROR from test-app: failed to load user");
+        assert_eq!(attachment["footer"], "test-app | env: prod | host: web-1");
+        assert_eq!(attachment["ts"], 1_700_000_000);
+        let fields: Vec<&Value> = attachment["fields"].as_array().unwrap().iter().map(|f| &f["title"]).collect();
+        assert_eq!(fields, vec!["Target Span", "Source", "user_id"]);
+        assert!(attachment["text"].as_str().unwrap().starts_with(">_failed to load user_\n*Caused by*"));
+
+        let mut inputs = self::inputs();
+        inputs.format = Some(MessageFormat::Attachments);
+        let settings = SlackSettings::default()
+            .level_color(tracing::Level::ERROR, 0x123456)
+            .footer("billing on-call");
+        inputs.platform = PlatformSettings::new(settings);
+        let payload: Value = serde_json::from_str(&SlackLayer::create(inputs).serialize()).unwrap();
+        assert_eq!(payload["attachments"][0]["color"], "#123456");
+        assert_eq!(payload["attachments"][0]["footer"], "billing on-call");
+    }
+
+    #[test]
+    fn plain_text_is_selectable_at_runtime() {
+        let mut inputs = inputs();
+        inputs.format = Some(MessageFormat::PlainText);
+        let payload: Value = serde_json::from_str(&SlackLayer::create(inputs).serialize()).unwrap();
+        assert!(payload.get("blocks").is_none());
+        assert_eq!(
+            payload["text"],
+            ":x: *ERROR* from *test-app*\n\
+             >_failed to load user_\n\
+             *Target Span*: `users::load`\n\
+             *Source*: `src/main.rs#L1`\n\
+             *Caused by*\n1. query failed\n2. connection reset\n\
+             *Metadata*:\n```\n{}\n```\n\
+             <!date^1700000000^{date_short_pretty} {time_secs}|2023-11-14T22:13:20+00:00> | env: *prod* | host: *web-1*"
+        );
+    }
+
+    #[cfg(feature = "blocks")]
+    #[test]
+    fn timestamp_and_context_are_shown_last() {
+        let payload: Value = serde_json::from_str(&SlackLayer::create(inputs()).serialize()).unwrap();
+        let blocks = &payload["blocks"];
+        let blocks = blocks.as_array().unwrap();
+        assert_eq!(blocks[blocks.len() - 2]["type"], "divider");
+        assert_eq!(
+            blocks[blocks.len() - 1]["elements"][0]["text"],
+            "<!date^1700000000^{date_short_pretty} {time_secs}|2023-11-14T22:13:20+00:00> | env: *prod* | host: *web-1*"
+        );
+    }
 }
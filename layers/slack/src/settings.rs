@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+use tracing::Level;
+use tracing_layer_core::thread::fingerprint;
+use tracing_layer_core::WebhookMessageInputs;
+
+/// The settings of Slack messages, set on the layer's builder with
+/// [`platform_settings`](crate::WebhookLayerBuilder::platform_settings).
+#[derive(Debug, Clone, Default)]
+pub struct SlackSettings {
+    mentions: Mentions,
+    broadcast_level: Option<Level>,
+    update_duplicates: bool,
+    upload_metadata_over: Option<usize>,
+    colors: BTreeMap<Level, u32>,
+    footer: Option<String>,
+    summary_fields: Vec<String>,
+    action_buttons: bool,
+}
+
+impl SlackSettings {
+    /// Mention someone in messages for events at or above the given level, e.g. [`Mention::Here`]
+    /// for errors. Each call adds a mention.
+    ///
+    /// [`Mention::Here`]: crate::Mention::Here
+    pub fn mention(mut self, level: Level, mention: impl Into<String>) -> Self {
+        self.mentions.add(None, level, mention.into());
+        self
+    }
+
+    /// Mention someone in messages for events of the given target and its submodules at or above
+    /// the given level, see [`mention`](Self::mention).
+    ///
+    /// The mentions for the most specific target matching an event replace those for less
+    /// specific ones, so a target may mention its owners instead of everyone, or mention nobody
+    /// below a level.
+    pub fn mention_target(mut self, target: impl Into<String>, level: Level, mention: impl Into<String>) -> Self {
+        self.mentions.add(Some(target.into()), level, mention.into());
+        self
+    }
+
+    /// Also show replies in a thread at or above the given level in the channel, e.g.
+    /// `Level::ERROR`. Replies are posted for the builder's `thread_by`.
+    pub fn broadcast_replies(mut self, level: Level) -> Self {
+        self.broadcast_level = Some(level);
+        self
+    }
+
+    /// Rather than posting duplicates of an event, from the same callsite with the same message,
+    /// update the first message to show how often and when it last occurred. Only messages posted
+    /// through the Web API can be updated.
+    pub fn update_duplicates(mut self) -> Self {
+        self.update_duplicates = true;
+        self
+    }
+
+    /// Upload metadata longer than `bytes` into the message's thread as a file, rather than
+    /// splitting it over several messages. Only messages posted through the Web API can have
+    /// files, and the bot needs the `files:write` scope.
+    pub fn upload_metadata_over(mut self, bytes: usize) -> Self {
+        self.upload_metadata_over = Some(bytes);
+        self
+    }
+
+    /// The color of the bar of attachments for events of the given level, as `0xRRGGBB`. Levels
+    /// without one keep their default color.
+    pub fn level_color(mut self, level: Level, color: u32) -> Self {
+        self.colors.insert(level, color);
+        self
+    }
+
+    /// Replace the footer of attachments, which names the application and its context by default.
+    pub fn footer(mut self, footer: impl Into<String>) -> Self {
+        self.footer = Some(footer.into());
+        self
+    }
+
+    /// Show this field of events or their current span, e.g. `user_id`, next to their target and
+    /// source, in blocks and attachments. Each call adds a field, shown in order. The field is
+    /// read from the event's metadata, so redacted and excluded fields are treated as they are
+    /// there.
+    pub fn summary_field(mut self, field: impl Into<String>) -> Self {
+        self.summary_fields.push(field.into());
+        self
+    }
+
+    /// Add buttons to acknowledge events and mute their callsite for a while to blocks. Clicks must
+    /// be handled by the application, which mutes callsites through the layer's `ReloadHandle`,
+    /// e.g. with the `interactive` feature's `InteractionHandler`.
+    pub fn action_buttons(mut self) -> Self {
+        self.action_buttons = true;
+        self
+    }
+
+    /// What these settings add to the message for an event.
+    pub(crate) fn options(&self, inputs: &WebhookMessageInputs) -> MessageOptions {
+        // Digests, which have no callsite, summarise quiet hours, so nobody is paged for them.
+        let mentions = if inputs.callsite.is_empty() {
+            Vec::new()
+        } else {
+            self.mentions.for_event(&inputs.target, inputs.event_level)
+        };
+        let update_key = (self.update_duplicates && !inputs.callsite.is_empty())
+            .then(|| fingerprint(&inputs.target, &inputs.source_file, inputs.source_line, &inputs.message));
+        MessageOptions {
+            mentions,
+            update_key,
+            upload_metadata_over: self.upload_metadata_over,
+            color: self.colors.get(&inputs.event_level).copied(),
+            footer: self.footer.clone(),
+            summary: self.summary(&inputs.metadata),
+            // Digests have no callsite to mute.
+            action_buttons: self.action_buttons && !inputs.callsite.is_empty(),
+            // More severe levels compare as smaller.
+            broadcast_reply: self.broadcast_level.is_some_and(|level| inputs.event_level <= level),
+        }
+    }
+
+    /// The values of the summary fields in an event's metadata, leaving out those it lacks.
+    fn summary(&self, metadata: &str) -> Vec<(String, String)> {
+        if self.summary_fields.is_empty() {
+            return Vec::new();
+        }
+        // The metadata of digests is a list rather than an object, and has no fields.
+        let fields: serde_json::Map<String, Value> = serde_json::from_str(metadata).unwrap_or_default();
+        self.summary_fields
+            .iter()
+            .filter_map(|field| {
+                let value = match fields.get(field)? {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                Some((field.clone(), value))
+            })
+            .collect()
+    }
+}
+
+/// The settings of the layer as they apply to a single message.
+pub(crate) struct MessageOptions {
+    /// Who to notify of the message, in Slack's syntax.
+    pub(crate) mentions: Vec<String>,
+    /// Relates the message to earlier duplicates, which update the first message instead of being
+    /// sent again.
+    pub(crate) update_key: Option<String>,
+    /// Metadata longer than this many bytes is uploaded as a file.
+    pub(crate) upload_metadata_over: Option<usize>,
+    /// The color of the bar of attachments, as `0xRRGGBB`, if not the level's default.
+    pub(crate) color: Option<u32>,
+    /// Replaces the default footer of attachments.
+    pub(crate) footer: Option<String>,
+    /// The summary fields the event has, as `(name, value)` pairs.
+    pub(crate) summary: Vec<(String, String)>,
+    /// Whether the message has buttons to acknowledge the event and mute its callsite.
+    pub(crate) action_buttons: bool,
+    /// Whether the message, when a reply in a thread, is also shown in the channel.
+    pub(crate) broadcast_reply: bool,
+}
+
+/// Who to mention in messages, by level and target.
+#[derive(Debug, Clone, Default)]
+struct Mentions {
+    rules: Vec<MentionRule>,
+}
+
+#[derive(Debug, Clone)]
+struct MentionRule {
+    /// The rule applies to events of this target and its submodules, or to every target if unset.
+    target: Option<String>,
+    /// The rule applies to events at or above this level.
+    level: Level,
+    mention: String,
+}
+
+impl Mentions {
+    fn add(&mut self, target: Option<String>, level: Level, mention: String) {
+        self.rules.push(MentionRule { target, level, mention });
+    }
+
+    /// The mentions for an event. The rules for the most specific target matching the event replace
+    /// those for less specific targets, including the rules for every target.
+    fn for_event(&self, target: &str, level: Level) -> Vec<String> {
+        let specificity = |rule: &MentionRule| match &rule.target {
+            None => Some(0),
+            Some(prefix) if target == prefix || target.starts_with(&format!("{}::", prefix)) => Some(prefix.len() + 1),
+            Some(_) => None,
+        };
+        let Some(most_specific) = self.rules.iter().filter_map(specificity).max() else {
+            return Vec::new();
+        };
+        let mut mentions: Vec<String> = Vec::new();
+        // More severe levels compare as smaller.
+        for rule in self
+            .rules
+            .iter()
+            .filter(|rule| specificity(rule) == Some(most_specific) && level <= rule.level)
+        {
+            if !mentions.contains(&rule.mention) {
+                mentions.push(rule.mention.clone());
+            }
+        }
+        mentions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_most_specific_target_decides() {
+        let mut mentions = Mentions::default();
+        mentions.add(None, Level::ERROR, "<!here>".to_string());
+        mentions.add(Some("app::payments".to_string()), Level::WARN, "<!subteam^S1>".to_string());
+        mentions.add(Some("app::payments".to_string()), Level::ERROR, "<!here>".to_string());
+
+        assert_eq!(mentions.for_event("app::users", Level::ERROR), vec!["<!here>"]);
+        assert!(mentions.for_event("app::users", Level::WARN).is_empty());
+        assert_eq!(
+            mentions.for_event("app::payments::stripe", Level::ERROR),
+            vec!["<!subteam^S1>", "<!here>"]
+        );
+        assert_eq!(mentions.for_event("app::payments", Level::WARN), vec!["<!subteam^S1>"]);
+        assert!(mentions.for_event("app::payments_v2", Level::WARN).is_empty());
+        assert!(mentions.for_event("app::payments", Level::INFO).is_empty());
+    }
+
+    #[test]
+    fn summary_fields_are_read_from_the_metadata() {
+        let settings = SlackSettings::default()
+            .summary_field("user")
+            .summary_field("api_token")
+            .summary_field("request_id")
+            .summary_field("missing");
+        let inputs = WebhookMessageInputs {
+            metadata: r#"{ "api_token": "[REDACTED]", "request_id": 7, "user": "alice" }"#.into(),
+            ..Default::default()
+        };
+        let summary = settings.options(&inputs).summary;
+        assert_eq!(
+            summary,
+            vec![
+                ("user".to_string(), "alice".to_string()),
+                ("api_token".to_string(), "[REDACTED]".to_string()),
+                ("request_id".to_string(), "7".to_string()),
+            ]
+        );
+
+        let digest = WebhookMessageInputs {
+            metadata: r#"[{ "user": "alice" }]"#.into(),
+            ..Default::default()
+        };
+        assert!(settings.options(&digest).summary.is_empty());
+    }
+
+    #[test]
+    fn digests_mention_nobody() {
+        let settings = SlackSettings::default().mention(Level::WARN, "<!here>");
+        let event = WebhookMessageInputs {
+            target: "app".into(),
+            callsite: "0123456789abcdef".into(),
+            ..Default::default()
+        };
+        assert_eq!(settings.options(&event).mentions, vec!["<!here>"]);
+
+        let digest = WebhookMessageInputs {
+            callsite: String::new(),
+            ..event
+        };
+        assert!(settings.options(&digest).mentions.is_empty());
+    }
+}
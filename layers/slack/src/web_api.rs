@@ -0,0 +1,589 @@
+//! Posting messages through the Slack [Web API](https://api.slack.com/web) with a bot token,
+//! rather than an incoming webhook.
+//!
+//! Unlike incoming webhooks, which are tied to a single channel, the Web API lets each message
+//! choose its channel; see [`SlackConfig::web_api`](crate::SlackConfig::web_api).
+use std::fmt;
+
+use serde::{de::DeserializeOwned, de::IgnoredAny, Deserialize};
+use tracing_layer_core::WebhookClient;
+
+/// The Web API used by [`SlackConfig::web_api`](crate::SlackConfig::web_api).
+pub const DEFAULT_API_URL: &str = "https://slack.com/api";
+
+/// An error reported by the Slack Web API in an `{"ok": false, "error": "..."}` response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlackApiError {
+    /// The bot token is invalid, revoked or missing.
+    InvalidAuth(String),
+    /// The channel does not exist, or the bot cannot see it.
+    ChannelNotFound,
+    /// The bot is not a member of the channel.
+    NotInChannel,
+    /// The channel has been archived.
+    IsArchived,
+    /// The message is too long, or its blocks are invalid.
+    InvalidMessage(String),
+    /// Too many requests were made with the token.
+    RateLimited,
+    /// Any other error code.
+    Other(String),
+    /// The response was not a Web API response.
+    InvalidResponse(String),
+    /// The request could not be sent, or was refused before reaching the API.
+    Request(String),
+}
+
+impl SlackApiError {
+    fn from_code(code: &str) -> Self {
+        match code {
+            "invalid_auth" | "not_authed" | "account_inactive" | "token_revoked" | "token_expired" => {
+                SlackApiError::InvalidAuth(code.to_string())
+            }
+            "channel_not_found" => SlackApiError::ChannelNotFound,
+            "not_in_channel" => SlackApiError::NotInChannel,
+            "is_archived" => SlackApiError::IsArchived,
+            "msg_too_long" | "no_text" | "invalid_blocks" | "invalid_blocks_format" | "too_many_attachments" => {
+                SlackApiError::InvalidMessage(code.to_string())
+            }
+            "ratelimited" | "rate_limited" => SlackApiError::RateLimited,
+            other => SlackApiError::Other(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for SlackApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlackApiError::InvalidAuth(code) => write!(f, "the bot token was rejected: {}", code),
+            SlackApiError::ChannelNotFound => write!(f, "the channel was not found"),
+            SlackApiError::NotInChannel => write!(f, "the bot is not a member of the channel"),
+            SlackApiError::IsArchived => write!(f, "the channel is archived"),
+            SlackApiError::InvalidMessage(code) => write!(f, "the message was rejected: {}", code),
+            SlackApiError::RateLimited => write!(f, "the request was rate limited"),
+            SlackApiError::Other(code) => write!(f, "the request failed: {}", code),
+            SlackApiError::InvalidResponse(body) => write!(f, "invalid slack api response: {}", body),
+            SlackApiError::Request(e) => write!(f, "the request could not be sent: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SlackApiError {}
+
+/// A message posted or updated through the Web API.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PostedMessage {
+    /// The id of the channel the message was posted to.
+    pub channel: String,
+    /// The timestamp identifying the message within its channel.
+    pub ts: String,
+}
+
+#[derive(Deserialize)]
+struct Status {
+    ok: bool,
+    error: Option<String>,
+}
+
+/// Parse a Web API response, reporting the error of unsuccessful ones.
+fn parse<T: DeserializeOwned>(body: &str) -> Result<T, SlackApiError> {
+    let invalid = || SlackApiError::InvalidResponse(body.to_string());
+    match serde_json::from_str(body).map_err(|_| invalid())? {
+        Status { ok: true, .. } => serde_json::from_str(body).map_err(|_| invalid()),
+        Status { error, .. } => Err(SlackApiError::from_code(error.as_deref().unwrap_or("unknown_error"))),
+    }
+}
+
+/// Parse the response to a `chat.postMessage` or `chat.update` request.
+pub fn parse_response(body: &str) -> Result<PostedMessage, SlackApiError> {
+    parse(body)
+}
+
+#[derive(Deserialize)]
+struct UploadUrl {
+    upload_url: String,
+    file_id: String,
+}
+
+/// Upload a file into the thread of a message, through the external upload flow: the content is
+/// sent to a URL reserved for the file, which is then shared in the channel.
+pub(crate) async fn upload_file(
+    client: &WebhookClient,
+    api_url: &str,
+    thread: &PostedMessage,
+    filename: &str,
+    content: &str,
+) -> Result<(), SlackApiError> {
+    let length = content.len().to_string();
+    let upload: UploadUrl = parse(
+        &request(
+            client
+                .post(&format!("{}/files.getUploadURLExternal", api_url))
+                .form(&[("filename", filename), ("length", length.as_str())]),
+        )
+        .await?,
+    )?;
+
+    request(client.post(&upload.upload_url).body(content.to_string())).await?;
+
+    let files = serde_json::json!([{ "id": upload.file_id, "title": filename }]).to_string();
+    parse::<IgnoredAny>(
+        &request(client.post(&format!("{}/files.completeUploadExternal", api_url)).form(&[
+            ("files", files.as_str()),
+            ("channel_id", thread.channel.as_str()),
+            ("thread_ts", thread.ts.as_str()),
+        ]))
+        .await?,
+    )?;
+    Ok(())
+}
+
+/// Send a request, returning the body of a successful response.
+async fn request(request: reqwest::RequestBuilder) -> Result<String, SlackApiError> {
+    let response = request
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|e| SlackApiError::Request(e.to_string()))?;
+    response.text().await.map_err(|e| SlackApiError::Request(e.to_string()))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::{Arc, Mutex},
+    };
+
+    use serde_json::Value;
+    use tracing::Level;
+    use tracing_layer_core::thread::ThreadBy;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::{EventFilters, MessageFormat, SlackConfig, SlackLayer, SlackSettings};
+
+    /// A request received by the [`StubApi`].
+    #[derive(Debug, Clone)]
+    pub(crate) struct Request {
+        pub(crate) path: String,
+        pub(crate) authorization: Option<String>,
+        pub(crate) body: Value,
+    }
+
+    /// A stand-in for the Slack Web API, answering every request with the queued responses, then
+    /// with successful ones.
+    #[derive(Clone, Default)]
+    pub(crate) struct StubApi {
+        pub(crate) requests: Arc<Mutex<Vec<Request>>>,
+        pub(crate) responses: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl StubApi {
+        /// Start serving, returning the API's URL.
+        pub(crate) fn start(&self) -> String {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap();
+            let api = self.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let api = api.clone();
+                    std::thread::spawn(move || api.serve(stream.unwrap()));
+                }
+            });
+            format!("http://{}", address)
+        }
+
+        fn serve(&self, stream: TcpStream) {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut stream = stream;
+            loop {
+                let mut request_line = String::new();
+                if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+                    return;
+                }
+                let (mut content_length, mut authorization, mut host) = (0, None, String::new());
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        } else if name.eq_ignore_ascii_case("authorization") {
+                            authorization = Some(value.trim().to_string());
+                        } else if name.eq_ignore_ascii_case("host") {
+                            host = value.trim().to_string();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                // Form and file bodies are kept as text.
+                let body = serde_json::from_slice(&body)
+                    .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).into_owned()));
+                let path = request_line.split_whitespace().nth(1).unwrap().to_string();
+
+                let response = {
+                    let mut requests = self.requests.lock().unwrap();
+                    let mut responses = self.responses.lock().unwrap();
+                    let response = if !responses.is_empty() {
+                        responses.remove(0)
+                    } else if path == "/files.getUploadURLExternal" {
+                        let upload_url = format!("http://{}/upload/F1", host);
+                        serde_json::json!({ "ok": true, "upload_url": upload_url, "file_id": "F1" }).to_string()
+                    } else if path.starts_with("/upload/") {
+                        format!("OK - {}", content_length)
+                    } else if path == "/files.completeUploadExternal" {
+                        serde_json::json!({ "ok": true, "files": [{ "id": "F1" }] }).to_string()
+                    } else {
+                        let ts = body["ts"]
+                            .as_str()
+                            .map(str::to_string)
+                            .unwrap_or_else(|| format!("1700000000.{:06}", requests.len() + 1));
+                        serde_json::json!({ "ok": true, "channel": body["channel"], "ts": ts }).to_string()
+                    };
+                    requests.push(Request {
+                        path,
+                        authorization,
+                        body,
+                    });
+                    response
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                    response.len(),
+                    response
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn errors_are_typed() {
+        assert_eq!(
+            parse_response(r#"{"ok":false,"error":"not_in_channel"}"#),
+            Err(SlackApiError::NotInChannel)
+        );
+        assert_eq!(
+            parse_response(r#"{"ok":false,"error":"invalid_auth"}"#),
+            Err(SlackApiError::InvalidAuth("invalid_auth".to_string()))
+        );
+        assert_eq!(
+            parse_response("ok"),
+            Err(SlackApiError::InvalidResponse("ok".to_string()))
+        );
+        assert_eq!(
+            parse_response(r#"{"ok":true,"channel":"C123","ts":"1700000000.000100"}"#),
+            Ok(PostedMessage {
+                channel: "C123".to_string(),
+                ts: "1700000000.000100".to_string()
+            })
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn messages_are_posted_with_the_bot_token() {
+        let api = StubApi::default();
+        let url = api.start();
+        let (layer, worker) = SlackLayer::builder("test-app".into(), EventFilters::default())
+            .config(SlackConfig::web_api("xoxb-test", "C123").api_url(url))
+            .channel_field("slack_channel")
+            .build();
+        let dispatch = tracing::Dispatch::new(tracing_subscriber::registry().with(layer));
+        worker.start().await;
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            tracing::error!("payment failed");
+            tracing::error!(slack_channel = "C999", "refund failed");
+        });
+        worker.flush().await;
+
+        let requests = api.requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|request| request.path == "/chat.postMessage"));
+        assert!(requests
+            .iter()
+            .all(|request| request.authorization.as_deref() == Some("Bearer xoxb-test")));
+        assert_eq!(requests[0].body["channel"], "C123");
+        assert_eq!(requests[1].body["channel"], "C999");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rerouted_webhooks_receive_no_token() {
+        use chrono::NaiveTime;
+        use tracing_layer_core::schedule::{QuietWindow, Schedule, ScheduleAction};
+
+        let (api, webhook) = (StubApi::default(), StubApi::default());
+        let url = api.start();
+        let webhook_url = format!("{}/services/T1/B1", webhook.start());
+        webhook.responses.lock().unwrap().push("ok".to_string());
+        let schedule = Schedule::new(chrono_tz::UTC).window(
+            QuietWindow::new(NaiveTime::MIN, NaiveTime::MIN).up_to(Level::WARN),
+            ScheduleAction::Reroute(webhook_url),
+        );
+        let (layer, worker) = SlackLayer::builder("test-app".into(), EventFilters::default())
+            .config(SlackConfig::web_api("xoxb-test", "C123").api_url(url))
+            .schedule(schedule)
+            .build();
+        let dispatch = tracing::Dispatch::new(tracing_subscriber::registry().with(layer));
+        worker.start().await;
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            tracing::warn!("payment retried");
+            tracing::error!("payment failed");
+        });
+        worker.flush().await;
+
+        let rerouted = webhook.requests.lock().unwrap().clone();
+        assert_eq!(rerouted.len(), 1);
+        assert_eq!(rerouted[0].path, "/services/T1/B1");
+        assert_eq!(rerouted[0].authorization, None);
+        assert_eq!(rerouted[0].body["channel"], Value::Null);
+        let requests = api.requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].authorization.as_deref(), Some("Bearer xoxb-test"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn related_events_are_replies_in_a_thread() {
+        let api = StubApi::default();
+        let url = api.start();
+        let (layer, worker) = SlackLayer::builder("test-app".into(), EventFilters::default())
+            .config(SlackConfig::web_api("xoxb-test", "C123").api_url(url))
+            .thread_by(ThreadBy::Field("request_id".to_string()))
+            .platform_settings(SlackSettings::default().broadcast_replies(Level::ERROR))
+            .build();
+        let dispatch = tracing::Dispatch::new(tracing_subscriber::registry().with(layer));
+        worker.start().await;
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            tracing::warn!(request_id = "r1", "retrying payment");
+            tracing::warn!(request_id = "r1", "retrying payment again");
+            tracing::error!(request_id = "r1", "payment failed");
+            tracing::error!(request_id = "r2", "refund failed");
+        });
+        worker.flush().await;
+
+        let requests = api.requests.lock().unwrap().clone();
+        let replies: Vec<(&Value, &Value)> = requests
+            .iter()
+            .map(|request| (&request.body["thread_ts"], &request.body["reply_broadcast"]))
+            .collect();
+        assert_eq!(
+            replies,
+            vec![
+                (&Value::Null, &Value::Null),
+                (&Value::from("1700000000.000001"), &Value::Null),
+                (&Value::from("1700000000.000001"), &Value::Bool(true)),
+                (&Value::Null, &Value::Null),
+            ]
+        );
+    }
+
+    #[cfg(feature = "blocks")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn follow_ups_of_replies_stay_in_the_thread() {
+        let api = StubApi::default();
+        let url = api.start();
+        let (layer, worker) = SlackLayer::builder("test-app".into(), EventFilters::default())
+            .config(SlackConfig::web_api("xoxb-test", "C123").api_url(url))
+            .thread_by(ThreadBy::Field("request_id".to_string()))
+            .build();
+        let dispatch = tracing::Dispatch::new(tracing_subscriber::registry().with(layer));
+        worker.start().await;
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            tracing::error!(request_id = "r1", "payment failed");
+            tracing::error!(request_id = "r1", response = %"x".repeat(200_000), "refund failed");
+        });
+        worker.flush().await;
+
+        let requests = api.requests.lock().unwrap().clone();
+        assert!(requests.len() > 2);
+        assert_eq!(requests[0].body["thread_ts"], Value::Null);
+        assert!(requests[1..]
+            .iter()
+            .all(|request| request.body["thread_ts"] == "1700000000.000001"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn threads_are_kept_per_channel() {
+        let api = StubApi::default();
+        let url = api.start();
+        let (layer, worker) = SlackLayer::builder("test-app".into(), EventFilters::default())
+            .config(SlackConfig::web_api("xoxb-test", "C123").api_url(url))
+            .channel_field("slack_channel")
+            .thread_by(ThreadBy::Field("request_id".to_string()))
+            .build();
+        let dispatch = tracing::Dispatch::new(tracing_subscriber::registry().with(layer));
+        worker.start().await;
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            tracing::error!(request_id = "r1", "payment failed");
+            tracing::error!(request_id = "r1", slack_channel = "C999", "refund failed");
+            tracing::error!(request_id = "r1", slack_channel = "C999", "refund retried");
+        });
+        worker.flush().await;
+
+        let requests = api.requests.lock().unwrap().clone();
+        let threads: Vec<(&Value, &Value)> = requests
+            .iter()
+            .map(|request| (&request.body["channel"], &request.body["thread_ts"]))
+            .collect();
+        assert_eq!(
+            threads,
+            vec![
+                (&Value::from("C123"), &Value::Null),
+                (&Value::from("C999"), &Value::Null),
+                (&Value::from("C999"), &Value::from("1700000000.000002")),
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn duplicates_update_the_first_message() {
+        let api = StubApi::default();
+        let url = api.start();
+        let (layer, worker) = SlackLayer::builder("test-app".into(), EventFilters::default())
+            .config(SlackConfig::web_api("xoxb-test", "#alerts").api_url(url))
+            .platform_settings(SlackSettings::default().update_duplicates())
+            .format(MessageFormat::Rich)
+            .build();
+        let dispatch = tracing::Dispatch::new(tracing_subscriber::registry().with(layer));
+        worker.start().await;
+        api.responses
+            .lock()
+            .unwrap()
+            .push(r#"{"ok":true,"channel":"C123","ts":"1700000000.000100"}"#.to_string());
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            for attempt in 0..3 {
+                tracing::error!(attempt, "payment failed");
+            }
+        });
+        worker.flush().await;
+
+        let requests = api.requests.lock().unwrap().clone();
+        let paths: Vec<&str> = requests.iter().map(|request| request.path.as_str()).collect();
+        assert_eq!(paths, vec!["/chat.postMessage", "/chat.update", "/chat.update"]);
+        let update = &requests[2].body;
+        assert_eq!(update["channel"], "C123");
+        assert_eq!(update["ts"], "1700000000.000100");
+        // The first event's details are kept, only its origin gains the count.
+        let (first, blocks) = (requests[0].body["blocks"].as_array().unwrap(), update["blocks"].as_array().unwrap());
+        assert_eq!(first[..first.len() - 1], blocks[..blocks.len() - 1]);
+        let origin = blocks.last().unwrap()["elements"].as_array().unwrap();
+        assert_eq!(origin.len(), 2);
+        assert!(origin[1]["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("Occurred *3* times, last at <!date^"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn duplicates_are_sent_when_updates_fail() {
+        let api = StubApi::default();
+        let url = api.start();
+        let (layer, worker) = SlackLayer::builder("test-app".into(), EventFilters::default())
+            .config(SlackConfig::web_api("xoxb-test", "C123").api_url(url))
+            .platform_settings(SlackSettings::default().update_duplicates())
+            .build();
+        let dispatch = tracing::Dispatch::new(tracing_subscriber::registry().with(layer));
+        worker.start().await;
+        api.responses.lock().unwrap().extend([
+            r#"{"ok":true,"channel":"C123","ts":"1700000000.000100"}"#.to_string(),
+            r#"{"ok":false,"error":"message_not_found"}"#.to_string(),
+        ]);
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            for _ in 0..3 {
+                tracing::error!("payment failed");
+            }
+        });
+        worker.flush().await;
+
+        let requests = api.requests.lock().unwrap().clone();
+        let paths: Vec<&str> = requests.iter().map(|request| request.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec!["/chat.postMessage", "/chat.update", "/chat.postMessage", "/chat.update"]
+        );
+        // Later duplicates update the message sent in place of the lost one.
+        assert_eq!(requests[3].body["ts"], "1700000000.000003");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn large_metadata_is_uploaded_into_the_thread() {
+        let api = StubApi::default();
+        let url = api.start();
+        let (layer, worker) = SlackLayer::builder("test-app".into(), EventFilters::default())
+            .config(SlackConfig::web_api("xoxb-test", "C123").api_url(url))
+            .platform_settings(SlackSettings::default().upload_metadata_over(100))
+            .build();
+        let dispatch = tracing::Dispatch::new(tracing_subscriber::registry().with(layer));
+        worker.start().await;
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            tracing::error!(short = "ok", "payment failed");
+            tracing::error!(response = %"x".repeat(200), "refund failed");
+        });
+        worker.flush().await;
+
+        let requests = api.requests.lock().unwrap().clone();
+        let paths: Vec<&str> = requests.iter().map(|request| request.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/chat.postMessage",
+                "/chat.postMessage",
+                "/files.getUploadURLExternal",
+                "/upload/F1",
+                "/files.completeUploadExternal",
+            ]
+        );
+        assert!(requests[1].body.to_string().contains("uploaded to the thread as `metadata.json`"));
+        assert_eq!(requests[3].body["response"], "x".repeat(200));
+        let complete = requests[4].body.as_str().unwrap();
+        assert!(complete.contains("channel_id=C123"));
+        assert!(complete.contains("thread_ts=1700000000.000002"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn metadata_rerouted_to_a_webhook_stays_in_the_message() {
+        use chrono::NaiveTime;
+        use tracing_layer_core::schedule::{QuietWindow, Schedule, ScheduleAction};
+
+        let (api, webhook) = (StubApi::default(), StubApi::default());
+        let url = api.start();
+        let webhook_url = format!("{}/services/T1/B1", webhook.start());
+        webhook.responses.lock().unwrap().push("ok".to_string());
+        let schedule = Schedule::new(chrono_tz::UTC).window(
+            QuietWindow::new(NaiveTime::MIN, NaiveTime::MIN).up_to(Level::WARN),
+            ScheduleAction::Reroute(webhook_url),
+        );
+        let (layer, worker) = SlackLayer::builder("test-app".into(), EventFilters::default())
+            .config(SlackConfig::web_api("xoxb-test", "C123").api_url(url))
+            .schedule(schedule)
+            .platform_settings(SlackSettings::default().upload_metadata_over(100))
+            .build();
+        let dispatch = tracing::Dispatch::new(tracing_subscriber::registry().with(layer));
+        worker.start().await;
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            tracing::warn!(response = %"x".repeat(200), "refund retried");
+        });
+        worker.flush().await;
+
+        assert!(api.requests.lock().unwrap().is_empty());
+        let rerouted = webhook.requests.lock().unwrap().clone();
+        assert_eq!(rerouted.len(), 1);
+        let body = rerouted[0].body.to_string();
+        assert!(body.contains(&"x".repeat(200)));
+        assert!(!body.contains("metadata.json"));
+    }
+}